@@ -0,0 +1,87 @@
+/* example krusty plugin written in C
+*
+* build with `make c_plugin` and load with `import_native("target/c_plugin/hello_c")`
+*
+*/
+
+#include <stdio.h>
+#include <string.h>
+
+#include "krusty_plugin.h"
+
+static const KrustyApi *api;
+
+
+static KrustyValue *greet(KrustyCall *call, size_t argc, const KrustyValue *const *argv, void *userdata) {
+    const char *greeting = userdata;
+    size_t len;
+    const char *name;
+    char buf[256];
+
+    if (argc != 1 || (name = api->as_text(argv[0], &len)) == NULL) {
+        api->raise_error(call, "greet() expects a text argument");
+        return NULL;
+    }
+    int n = snprintf(buf, sizeof(buf), "%s, %s!", greeting, name);
+    return api->new_text(buf, n < (int)sizeof(buf) ? (size_t)n : sizeof(buf) - 1);
+}
+
+
+static KrustyValue *text_length(KrustyCall *call, size_t argc, const KrustyValue *const *argv, void *userdata) {
+    /* length in bytes of the string handed to C */
+    size_t len;
+    (void)userdata;
+
+    if (argc != 1 || api->as_text(argv[0], &len) == NULL) {
+        api->raise_error(call, "text_length() expects a text argument");
+        return NULL;
+    }
+    return api->new_number((double)len);
+}
+
+
+static KrustyValue *add(KrustyCall *call, size_t argc, const KrustyValue *const *argv, void *userdata) {
+    double total = 0;
+    int ok;
+    (void)userdata;
+
+    for (size_t i = 0; i < argc; i++) {
+        total += api->as_number(argv[i], &ok);
+        if (!ok) {
+            api->raise_error(call, "add() expects numbers");
+            return NULL;
+        }
+    }
+    return api->new_number(total);
+}
+
+
+static KrustyValue *apply(KrustyCall *call, size_t argc, const KrustyValue *const *argv, void *userdata) {
+    /* apply(func, a, b, ...) calls func with the remaining arguments */
+    (void)userdata;
+
+    if (argc < 1 || api->type_of(argv[0]) != KRUSTY_TYPE_FUNC) {
+        api->raise_error(call, "apply() expects a function");
+        return NULL;
+    }
+    return api->call(call, argv[0], argc - 1, argv + 1);
+}
+
+
+KRUSTY_EXPORT uint32_t krusty_plugin_abi_version(void) {
+    return KRUSTY_ABI_VERSION;
+}
+
+KRUSTY_EXPORT int krusty_plugin_init(const KrustyApi *krusty, KrustyModule *module) {
+    if (krusty->abi_version != KRUSTY_ABI_VERSION) {
+        return 1;
+    }
+    api = krusty;
+
+    api->set_function(module, "greet", greet, "Hello");
+    api->set_function(module, "text_length", text_length, NULL);
+    api->set_function(module, "add", add, NULL);
+    api->set_function(module, "apply", apply, NULL);
+    api->set_value(module, "language", api->new_text("C", 1));
+    return 0;
+}
@@ -0,0 +1,100 @@
+// embedding krusty in a rust application
+// cargo run -p krusty-core --example embed
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use krusty_core::Interpreter;
+use krusty_core::lib::errors::KrustyErrorType;
+use krusty_core::lib::convert::IntoKrusty;
+use krusty_core::lib::streams::CaptureBuffer;
+
+
+fn greet(name: String, times: i64) -> Result<Vec<String>, KrustyErrorType> {
+    Ok((0..times).map(|i| format!("{} hello {}", i, name)).collect())
+}
+
+
+fn tag(mut fields: HashMap<String, f64>) -> Result<HashMap<String, f64>, KrustyErrorType> {
+    fields.insert("_seen".to_string(), 1.0);
+    Ok(fields)
+}
+
+
+fn run() -> Result<(), KrustyErrorType> {
+    // a missing root directory is reported instead of panicking
+    match Interpreter::with_root(Path::new("no/such/dir")) {
+        Err(e) => println!("error: {}", e.msg()),
+        Ok(_) => unreachable!(),
+    }
+
+    let mut interp = Interpreter::new();
+    interp.register_typed("greet", greet);
+    interp.register_typed("tag", tag);
+    interp.set_global("limit", 3.0);
+
+    interp.eval_str("
+        lines = greet('host', limit);
+        square = (x) => { x * x };
+    ")?;
+
+    let lines: Vec<String> = interp.get_global_as("lines")?;
+    assert_eq!(lines.len(), 3);
+    println!("{:?}", lines);
+
+    let squared = interp.call_function("square", vec![7.0.into_krusty()])?;
+    println!("square(7) = {}", squared);
+
+    // maps keep every key, including ones a script module would treat as private or reserved
+    interp.eval_str("
+        tagged = tag(json.parse('{\"_id\": 7, \"__type__\": 1}'));
+        assert(tagged._seen == 1);
+        assert(type(tagged) == types.Module);
+    ")?;
+    let tagged: HashMap<String, f64> = interp.get_global_as("tagged")?;
+    assert_eq!(tagged.len(), 3);
+    assert_eq!(tagged["_id"], 7.0);
+
+    // script output can be captured instead of going to the terminal
+    let out = CaptureBuffer::new();
+    interp.set_stdout(out.clone());
+    interp.set_stdin("krusty\n".as_bytes());
+    interp.eval_str("name = input('name? '); print('hi', name);")?;
+    assert_eq!(out.text(), "name? hi krusty\n");
+
+    match interp.eval_str("greet('host');") {
+        Err(e) => println!("error: {}", e.msg()),
+        Ok(_) => unreachable!(),
+    }
+
+    // another thread can stop a running script, which sees a KeyboardInterrupt that `try` can catch
+    let handle = interp.interrupt_handle();
+    let stopper = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        handle.interrupt();
+    });
+    interp.eval_str("
+        spin = (n) => { ret spin(n + 1); };
+        caught = try(() => {
+            spin(0);
+            ret false;
+        }, () => {
+            ret true;
+        });
+    ")?;
+    stopper.join().expect("interrupt thread panicked");
+    let caught: bool = interp.get_global_as("caught")?;
+    assert!(caught);
+    println!("interrupted and caught");
+    Ok(())
+}
+
+
+fn main() {
+    if let Err(e) = run() {
+        e.print_traceback();
+        std::process::exit(1);
+    }
+}
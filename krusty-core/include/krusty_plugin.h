@@ -0,0 +1,88 @@
+/* Krusty native plugin ABI
+*
+* A plugin is a shared library exporting
+*
+*   uint32_t krusty_plugin_abi_version(void);    // return KRUSTY_ABI_VERSION
+*   int krusty_plugin_init(const KrustyApi *api, KrustyModule *module);    // return 0 on success
+*
+* `krusty_plugin_init` registers the module members with `api->set_function` and `api->set_value`.
+* The api pointer stays valid for the life of the process and may be stored by the plugin.
+*
+* Ownership
+*   - values returned by new_*, list_get, clone and call are owned by the caller and released with `free`
+*   - list_push and set_value take ownership of the value passed in, even when they fail
+*   - arguments passed to a native function are borrowed for the duration of the call
+*   - as_text returns a NUL terminated string owned by the value handle, interior NULs are dropped
+*     and len is set to the length of that string
+*
+* A native function returns a new value, or NULL. NULL after `raise_error` raises an error in
+* the calling script, otherwise it stands for krusty's `null`
+*
+*/
+
+#ifndef KRUSTY_PLUGIN_H
+#define KRUSTY_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define KRUSTY_ABI_VERSION 1
+
+#if defined(_WIN32)
+#define KRUSTY_EXPORT __declspec(dllexport)
+#else
+#define KRUSTY_EXPORT __attribute__((visibility("default")))
+#endif
+
+enum KrustyType {
+    KRUSTY_TYPE_NULL = 0,
+    KRUSTY_TYPE_BOOL = 1,
+    KRUSTY_TYPE_NUMBER = 2,
+    KRUSTY_TYPE_TEXT = 3,
+    KRUSTY_TYPE_LIST = 4,
+    KRUSTY_TYPE_FUNC = 5,
+    KRUSTY_TYPE_MODULE = 6,
+    KRUSTY_TYPE_OTHER = 7,
+};
+
+typedef struct KrustyValue KrustyValue;
+typedef struct KrustyCall KrustyCall;
+typedef struct KrustyModule KrustyModule;
+
+typedef KrustyValue *(*KrustyNativeFn)(
+    KrustyCall *call, size_t argc, const KrustyValue *const *argv, void *userdata);
+
+/* new entries are only ever appended. Check `size` before using entries added after version 1 */
+typedef struct KrustyApi {
+    uint32_t abi_version;
+    uint32_t size;
+
+    KrustyValue *(*new_null)(void);
+    KrustyValue *(*new_bool)(int b);
+    KrustyValue *(*new_number)(double n);
+    KrustyValue *(*new_text)(const char *s, size_t len);    /* invalid utf-8 is replaced */
+    KrustyValue *(*new_list)(void);
+    int (*list_push)(KrustyValue *list, KrustyValue *item);    /* 1 on success */
+    size_t (*list_len)(const KrustyValue *list);
+    KrustyValue *(*list_get)(const KrustyValue *list, size_t idx);    /* NULL when out of range */
+    KrustyValue *(*clone)(const KrustyValue *v);
+    void (*free)(KrustyValue *v);
+    int (*type_of)(const KrustyValue *v);
+    double (*as_number)(const KrustyValue *v, int *ok);
+    int (*as_bool)(const KrustyValue *v, int *ok);
+    const char *(*as_text)(const KrustyValue *v, size_t *len);    /* NULL when not text */
+    void (*raise_error)(KrustyCall *call, const char *msg);
+    KrustyValue *(*call)(KrustyCall *call, const KrustyValue *func, size_t argc, const KrustyValue *const *argv);
+    int (*set_function)(KrustyModule *module, const char *name, KrustyNativeFn func, void *userdata);
+    int (*set_value)(KrustyModule *module, const char *name, KrustyValue *value);
+} KrustyApi;
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KRUSTY_PLUGIN_H */
@@ -0,0 +1,156 @@
+/* this file provides `Interpreter`, the entry point for applications embedding krusty
+*
+* let mut interp = Interpreter::new();
+* interp.register_typed("double", |n: f64| Ok(n * 2.0));
+* interp.eval_str("x = double(21);")?;
+* let x: f64 = interp.get_global_as("x")?;
+*
+* The interpreter owns its top level namespace, so it can be stored in other structs.
+* Globals, imported modules and the search path persist across calls
+*
+*/
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::syntax::{lexer, parser::{self, Block}, evaluator::NameSpace};
+use crate::lib::{convert, funcdef, helper, sandbox::Sandbox, limits::{InterruptHandle, Limits}};
+use crate::lib::convert::{FromKrusty, IntoKrusty};
+use crate::lib::errors::{Error, KrustyErrorType};
+
+
+pub struct Interpreter {
+    ns: NameSpace<'static>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        // relative imports are resolved from the current directory, if it still exists
+        let cwd = env::current_dir().unwrap_or(PathBuf::from("."));
+        Interpreter::with_root(&cwd).unwrap_or_else(|_| Interpreter {
+            ns: NameSpace::new(None, None),
+        })
+    }
+
+    pub fn with_root(root: &Path) -> Result<Interpreter, KrustyErrorType> {
+        // `root` is the file or directory that relative imports and `krusty_modules` are looked up from
+        let root = match fs::canonicalize(root) {
+            Ok(r) => r,
+            Err(e) => generic_error!(format!("interpreter root {}: {}", root.display(), e))
+        };
+        Ok(Interpreter {
+            ns: NameSpace::new(Some(&root), None),
+        })
+    }
+
+    pub fn namespace(&mut self) -> &mut NameSpace<'static> {
+        &mut self.ns
+    }
+
+    pub fn add_search_path<P: AsRef<Path>>(&mut self, dir: P) {
+        // searched before the default locations
+        self.ns.get_search_path().borrow_mut().insert(0, dir.as_ref().to_path_buf());
+    }
+
+    pub fn set_sandbox(&mut self, sandbox: Sandbox) {
+        self.ns.set_sandbox(sandbox);
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        // checked by the evaluator, exceeding any of them raises a LimitError
+        self.ns.set_limits(limits);
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        // `handle.interrupt()` from any thread stops the running evaluation with a KeyboardInterrupt error
+        self.ns.get_limiter().borrow().interrupt_handle()
+    }
+
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        // lets several interpreters share one handle, e.g. a process wide Ctrl+C handler
+        self.ns.get_limiter().borrow_mut().set_interrupt_handle(handle);
+    }
+
+    // `print`, `eprint` and `input` use these streams. `streams::CaptureBuffer` collects output in memory
+
+    pub fn set_stdout<W: Write + 'static>(&mut self, out: W) {
+        self.ns.get_streams().borrow_mut().stdout = Box::new(out);
+    }
+
+    pub fn set_stderr<W: Write + 'static>(&mut self, err: W) {
+        self.ns.get_streams().borrow_mut().stderr = Box::new(err);
+    }
+
+    pub fn set_stdin<R: BufRead + 'static>(&mut self, input: R) {
+        self.ns.get_streams().borrow_mut().stdin = Box::new(input);
+    }
+
+    pub fn eval_str(&mut self, code: &str) -> Result<Block, KrustyErrorType> {
+        let mut tokens = lexer::lex(&code.to_string())?;
+        let tree = parser::parse(&mut tokens)?;
+        self.ns.run(&tree)
+    }
+
+    pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Block, KrustyErrorType> {
+        // runs the file in the global namespace, with imports relative to the file
+        let path = match fs::canonicalize(path.as_ref()) {
+            Ok(p) => p,
+            Err(e) => generic_error!(format!("{:?}: {}", path.as_ref(), e))
+        };
+        let mut tokens = lexer::lex_file(&path)?;
+        let tree = parser::parse(&mut tokens)?;
+
+        let root = self.ns.module.path.replace(path);
+        let res = self.ns.run(&tree);
+        self.ns.module.path = root;
+        res
+    }
+
+    pub fn call_function(&mut self, name: &str, args: Vec<Block>) -> Result<Block, KrustyErrorType> {
+        let name = name.to_string();
+        let func = self.ns.get(&name)?;
+        self.ns.get_limiter().borrow_mut().begin();
+        match func {
+            Block::Func(_) | Block::NativeFunc(_) => self.ns.eval_func_obj(&func, &Block::List(args), Some(&name)),
+            _ => eval_error!(format!("'{}' is not a function", name))
+        }
+    }
+
+    pub fn set_global<T: IntoKrusty>(&mut self, name: &str, value: T) {
+        self.ns.module.vars.insert(name.to_string(), value.into_krusty());
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Block> {
+        self.ns.module.vars.get(name).cloned()
+    }
+
+    pub fn get_global_as<T: FromKrusty>(&self, name: &str) -> Result<T, KrustyErrorType> {
+        match self.get_global(name) {
+            Some(b) => T::from_krusty(&b),
+            None => eval_error!(format!("Symbol '{}' not found", name))
+        }
+    }
+
+    // host functions are registered as builtins, so imported modules can use them too
+
+    pub fn register_fn(&mut self, name: &str, f: funcdef::NativeFuncType) {
+        helper::load_func(self.ns.builtins_mut(), name, f);
+    }
+
+    pub fn register_closure<F>(&mut self, name: &str, f: F)
+        where F: Fn(&mut NameSpace, &Vec<Block>) -> Result<Block, KrustyErrorType> + 'static {
+        helper::load_closure(self.ns.builtins_mut(), name, f);
+    }
+
+    pub fn register_typed<Args, F: convert::TypedNativeFunc<Args>>(&mut self, name: &str, f: F) {
+        helper::load_typed(self.ns.builtins_mut(), name, f);
+    }
+}
@@ -19,9 +19,26 @@ pub mod lib {
     pub mod builtins;
     pub mod errors;
     pub mod pkg;
+    pub mod manifest;
+    pub mod archive;
+    pub mod ffi;
+    pub mod convert;
+    pub mod streams;
+    pub mod sandbox;
+    pub mod limits;
+    pub mod list;
+    pub mod string;
+    pub mod math;
+    pub mod json;
+    pub mod random;
+    pub mod re;
 }
 
 
+pub mod interpreter;
+pub use interpreter::Interpreter;
+
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -0,0 +1,136 @@
+/* this file handles installable package archives
+*
+* `krusty pkg pack <dir>` creates `<name>-<version>.tar.gz` from a package directory with a manifest,
+* along with a `<name>-<version>.tar.gz.sha256` checksum file in the `sha256sum` format.
+* `--install` compares the archive with that checksum before extracting it. This only catches
+* corrupted or truncated copies: the checksum travels with the archive, so it says nothing about who made it
+*
+*/
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use sha2::{Digest, Sha256};
+
+use crate::lib::errors::{Error, KrustyErrorType};
+use crate::lib::manifest::Manifest;
+use crate::lib::pkg;
+
+pub const ARCHIVE_EXT: &str = "tar.gz";
+pub const CHECKSUM_EXT: &str = "sha256";
+
+
+pub fn is_pkg_archive(path: &Path) -> bool {
+    path.is_file() && path.to_string_lossy().ends_with(&format!(".{}", ARCHIVE_EXT))
+}
+
+pub fn checksum_path(archive: &Path) -> PathBuf {
+    let mut name = archive.as_os_str().to_owned();
+    name.push(format!(".{}", CHECKSUM_EXT));
+    PathBuf::from(name)
+}
+
+pub fn sha256_file(path: &Path) -> Result<String, KrustyErrorType> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), KrustyErrorType> {
+    // hidden files (.git etc.) are not part of the package
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+
+pub fn pack_pkg(dir: &Path, out_dir: &Path) -> Result<PathBuf, KrustyErrorType> {
+    // archives every file of the package directory, paths relative to the package root
+    let dir = fs::canonicalize(dir)?;
+    if !Manifest::exists_in(&dir) {
+        generic_error!(format!("{:?} has no manifest. Only versioned packages can be packed", dir))
+    }
+    let manifest = Manifest::from_dir(&dir)?;
+    manifest.validate(&dir)?;
+
+    let archive_path = out_dir.join(format!("{}-{}.{}", manifest.package.name, manifest.package.version, ARCHIVE_EXT));
+    println!("Packing {} {}", manifest.package.name, manifest.package.version);
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    collect_files(&dir, &mut files)?;
+
+    let encoder = GzEncoder::new(fs::File::create(&archive_path)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for f in &files {
+        let rel = f.strip_prefix(&dir).or(Err("invalid package file"))?;
+        println!("  add: {:?}", rel);
+        builder.append_path_with_name(f, rel)?;
+    }
+    builder.into_inner()?.finish()?;
+
+    let checksum = sha256_file(&archive_path)?;
+    let archive_name = archive_path.file_name().ok_or("filename not valid")?.to_string_lossy().to_string();
+    let mut sum_file = fs::File::create(checksum_path(&archive_path))?;
+    sum_file.write_all(format!("{}  {}\n", checksum, archive_name).as_bytes())?;
+    println!("  sha256: {}", checksum);
+    Ok(archive_path)
+}
+
+
+pub fn check_corruption(archive: &Path) -> Result<(), KrustyErrorType> {
+    let sum_path = checksum_path(archive);
+    let expected = match fs::read_to_string(&sum_path) {
+        Ok(t) => t.split_whitespace().next().unwrap_or("").to_lowercase(),
+        Err(_) => generic_error!(format!("checksum file {:?} not found", sum_path))
+    };
+    let actual = sha256_file(archive)?;
+    if expected != actual {
+        generic_error!(format!("checksum mismatch for {:?}: expected {}, found {}", archive, expected, actual))
+    }
+    println!("  checksum matches: {}", actual);
+    Ok(())
+}
+
+
+pub fn unpack_pkg(archive: &Path) -> Result<PathBuf, KrustyErrorType> {
+    // checks and extracts the archive into the install cache, returning the package directory
+    check_corruption(archive)?;
+
+    let name = archive.file_name().ok_or("filename not valid")?.to_string_lossy().to_string();
+    let stem = name.trim_end_matches(&format!(".{}", ARCHIVE_EXT));
+
+    let mut dest = pkg::get_install_path()?;
+    dest.push(pkg::CACHE_SUBFOLDER);
+    dest.push(stem);
+    if dest.exists() {
+        fs::remove_dir_all(&dest)?;
+    }
+    fs::create_dir_all(&dest)?;
+
+    let mut tarball = tar::Archive::new(GzDecoder::new(fs::File::open(archive)?));
+    tarball.unpack(&dest)?; // entries escaping the destination are skipped by unpack
+    println!("  extract: {:?}", dest);
+    Ok(dest)
+}
@@ -1,57 +1,151 @@
 #[cfg(debug_assertions)]
 use std::env; // required for print_verbose! macro
+use std::path::PathBuf;
+use std::io::Write;
 
 use crate::syntax::{lexer, lexer::Token};
 use crate::syntax::{parser, parser::Block};
 use crate::syntax::evaluator::NameSpace;
 
 use super::errors::{Error, KrustyErrorType};
-use super::moddef::ModuleVars;
+use super::moddef::{Module, ModuleVars};
 use super::helper;
 use super::pkg;
+use super::{convert, json, list, math, random, re, string};
 
 // ================ print =======================
 
 
-fn _print(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
-    match args.len() {
-        0 => println!(""),
-        _ => {
-            for idx in 0..args.len() {
-                if idx > 0 {
-                    print!(" ");
-                }
-                match &args[idx] {
-                    Block::Object(Token::Number(n)) => print!("{}", n),
-                    Block::Object(Token::Text(t)) => {
-                        // FIXME: this is a hack to implement unicode newline and tab characters
-                        print!("{}", t.replace("\\n", "\u{000A}").replace("\\t", "\u{0009}"))
-                    },
-                    _ => print!("{}", args[idx]),
-                };
-            };
-            print!("\n");
-        },
+fn _format_print(args: &Vec<Block>) -> String {
+    let mut out = String::new();
+    for (idx, arg) in args.iter().enumerate() {
+        if idx > 0 {
+            out.push(' ');
+        }
+        match arg {
+            Block::Object(Token::Number(n)) => out.push_str(&n.to_string()),
+            Block::Object(Token::Text(t)) => {
+                // FIXME: this is a hack to implement unicode newline and tab characters
+                out.push_str(&t.replace("\\n", "\u{000A}").replace("\\t", "\u{0009}"))
+            },
+            _ => out.push_str(&arg.to_string()),
+        };
     };
+    out
+}
+
+fn _print(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    let mut streams = ns.get_streams().borrow_mut();
+    writeln!(streams.stdout, "{}", _format_print(args))?;
+    streams.stdout.flush()?;
+    Ok(Block::Null)
+}
+
+fn _eprint(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    let mut streams = ns.get_streams().borrow_mut();
+    writeln!(streams.stderr, "{}", _format_print(args))?;
+    streams.stderr.flush()?;
     Ok(Block::Null)
 }
 
+fn _input(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // reads one line without the line ending. Returns null at the end of input
+    func_nargs_le!(args, 1);
+    let mut streams = ns.get_streams().borrow_mut();
+    if !args.is_empty() {
+        write!(streams.stdout, "{}", _format_print(args))?;
+        streams.stdout.flush()?;
+    }
+    let mut line = String::new();
+    if streams.stdin.read_line(&mut line)? == 0 {
+        return Ok(Block::Null)
+    }
+    let trimmed = line.trim_end_matches(&['\n', '\r'] as &[_]).len();
+    line.truncate(trimmed);
+    Ok(Block::Object(Token::Text(line)))
+}
+
 
 fn _type(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
     func_nargs_eq!(args, 1);
-    Ok(match args[0] {
-        Block::Object(Token::Text(_)) => Block::Object(Token::Text("<Text>".to_string())),
-        Block::Object(Token::Number(_)) => Block::Object(Token::Text("<Number>".to_string())),
-        Block::Func(_) => Block::Object(Token::Text("<Func>".to_string())),
-        Block::NativeFunc(_) => Block::Object(Token::Text("<NativeFunc>".to_string())),
-        Block::List(_) => Block::Object(Token::Text("<List>".to_string())),
-        Block::Bool(_) => Block::Object(Token::Text("<Bool>".to_string())),
-        Block::Expr(_) => Block::Object(Token::Text("<Expr>".to_string())),
-        Block::FuncBody(_) => Block::Object(Token::Text("<FuncBody>".to_string())),
-        Block::Mod(_) => Block::Object(Token::Text("<Module>".to_string())),
-        Block::Null => Block::Object(Token::Text("<Null>".to_string())),
-        _ => Block::Object(Token::Text("<Type Not Found>".to_string()))
-    })
+    Ok(Block::Type(convert::type_of(&args[0])))
+}
+
+fn _num(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    let n = match &args[0] {
+        Block::Object(Token::Number(n)) => *n,
+        Block::Object(Token::Text(t)) => match t.trim().parse::<f64>() {
+            Ok(n) => n,
+            Err(_) => eval_error!(format!("num() cannot convert '{}' to Number", t))
+        },
+        Block::Bool(b) => if *b {1.0} else {0.0},
+        b => eval_error!(format!("num() cannot convert {} to Number", convert::type_name(b)))
+    };
+    Ok(Block::Object(Token::Number(n)))
+}
+
+fn _str(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    Ok(Block::Object(Token::Text(string::to_text(&args[0]))))
+}
+
+fn _bool(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // null, 0, empty text and empty lists are false
+    func_nargs_eq!(args, 1);
+    let b = match &args[0] {
+        Block::Bool(b) => *b,
+        Block::Null => false,
+        Block::Object(Token::Number(n)) => *n != 0.0 && !n.is_nan(),
+        Block::Object(Token::Text(t)) => !t.is_empty(),
+        Block::List(l) => !l.is_empty(),
+        Block::Func(_) | Block::NativeFunc(_) | Block::Mod(_) | Block::Type(_) => true,
+        b => eval_error!(format!("bool() cannot convert {} to Bool", convert::type_name(b)))
+    };
+    Ok(Block::Bool(b))
+}
+
+fn _list(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // text becomes its characters, a module its (name, value) pairs sorted by name
+    func_nargs_eq!(args, 1);
+    let l = match &args[0] {
+        Block::List(l) => l.clone(),
+        Block::Null => vec![],
+        Block::Object(Token::Text(t)) => t.chars().map(|c| Block::Object(Token::Text(c.to_string()))).collect(),
+        Block::Mod(m) => {
+            let mut pairs: Vec<(String, Block)> = m.public_vars().into_iter().collect();
+            pairs.sort_by(|a, b| a.0.cmp(&b.0));
+            pairs.into_iter()
+                .map(|(k, v)| Block::List(vec![Block::Object(Token::Text(k)), v]))
+                .collect()
+        },
+        b => eval_error!(format!("list() cannot convert {} to List", convert::type_name(b)))
+    };
+    Ok(Block::List(l))
+}
+
+fn _is_number(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    Ok(Block::Bool(matches!(args[0], Block::Object(Token::Number(_)))))
+}
+
+fn _is_text(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    Ok(Block::Bool(matches!(args[0], Block::Object(Token::Text(_)))))
+}
+
+fn _is_callable(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    Ok(Block::Bool(matches!(args[0], Block::Func(_) | Block::NativeFunc(_))))
+}
+
+fn _load_types(env_native: &mut ModuleVars) {
+    // `type(x) == types.Number`
+    let mut m = Module::new(None);
+    for t in convert::BUILTIN_TYPES {
+        m.vars.insert(t.to_string(), Block::Type(convert::TypeValue::Builtin(t)));
+    }
+    env_native.insert("types".to_string(), Block::Mod(m));
 }
 
 // ================ if =======================
@@ -71,24 +165,63 @@ fn _if(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
 
 // ================ import ================
 
+fn _exec_module(ns: &mut NameSpace, p: &PathBuf) -> Result<Module, KrustyErrorType> {
+    let mut tokens = lexer::lex_file(p)?;
+    let tree = parser::parse(&mut tokens)?;
+
+    let mut new_ns = NameSpace::new(Some(p), Some(ns));
+    new_ns.run(&tree)?;
+    Ok(new_ns.module)
+}
+
+
+fn _load_module(ns: &mut NameSpace, p: &PathBuf) -> Result<Block, KrustyErrorType> {
+    // executes the module file and stores the result in the interpreter's module cache
+    ns.get_module_cache().borrow_mut().begin_import(p)?;
+    let res = _exec_module(ns, p);
+    ns.get_module_cache().borrow_mut().end_import(p);
+
+    let module = res?;
+    ns.get_module_cache().borrow_mut().insert(p, module.clone());
+    Ok(Block::Mod(module))
+}
+
+
 fn _import(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
     func_nargs_eq!(args, 1);
     match &args[0] {
         Block::Object(Token::Text(p)) => {
             let p = pkg::search_for_module(ns, p)?;
             print_verbose!("import({:?})", p);
-            let mut tokens = lexer::lex_file(&p)?;
-            let tree = parser::parse(&mut tokens)?;
-
-            let mut new_ns = NameSpace::new(Some(&p), Some(ns));
-            new_ns.run(&tree)?;
-            new_ns.to_block()
+            ns.get_sandbox().check_import(&p)?;
+            let cached = ns.get_module_cache().borrow().get(&p);
+            match cached {
+                Some(m) => Ok(Block::Mod(m)),
+                None => _load_module(ns, &p)
+            }
         },
         _ => Ok(Block::Null)
     }
 }
 
 
+fn _reload(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    let p = match &args[0] {
+        Block::Object(Token::Text(p)) => pkg::search_for_module(ns, p)?,
+        Block::Mod(m) => match &m.path {
+            Some(p) if p.extension().is_some_and(|e| e == pkg::LANGUAGE_EXT) => p.clone(),
+            Some(_) => import_error!("native modules cannot be reloaded"),
+            None => import_error!("module was not imported from a file"),
+        },
+        _ => eval_error!("Unsupported argument to reload")
+    };
+    print_verbose!("reload({:?})", p);
+    ns.get_sandbox().check_import(&p)?;
+    _load_module(ns, &p)
+}
+
+
 
 fn _import_native(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
     func_nargs_eq!(args, 1);
@@ -99,9 +232,13 @@ fn _import_native(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, Krusty
             pkg::to_native_dylib_name(&mut p)?;
 
             print_verbose!("import_native({:?})", p);
+            if !p.is_file() {
+                import_error!(format!("{:?}: native library not found", p))
+            }
+            ns.get_sandbox().check_native(&p)?;
 
             let mut new_ns = NameSpace::new(Some(&p), Some(ns));
-            new_ns.module.load_dylib();
+            new_ns.module.load_dylib()?;
             new_ns.to_block()
         },
         _ => Ok(Block::Null)
@@ -113,20 +250,84 @@ fn _spill(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorTyp
     func_nargs_eq!(args, 1);
     match &args[0] {
         Block::Mod(m) => {
-            ns.module.vars.extend(m.vars.clone());
+            ns.module.vars.extend(m.public_vars());
         },
         _ => eval_error!("Unsupported argument to spill")
     };
     Ok(Block::Null)
 }
 
+
+fn _search_path(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // search_path() returns the module search directories
+    // search_path(dirs) replaces them. search_path(null) clears them
+    if !args.is_empty() {
+        let dirs = match &args[0] {
+            Block::List(l) if args.len() == 1 => l.clone(),
+            Block::Null if args.len() == 1 => vec![],
+            _ => args.clone(),
+        };
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for d in dirs {
+            match d {
+                Block::Object(Token::Text(t)) => paths.push(PathBuf::from(t)),
+                _ => eval_error!("search path entries should be text")
+            }
+        }
+        *ns.get_search_path().borrow_mut() = paths;
+    }
+    let paths = ns.get_search_path().borrow().iter()
+        .map(|p| Block::Object(Token::Text(p.to_string_lossy().to_string())))
+        .collect();
+    Ok(Block::List(paths))
+}
+
+
+fn _from(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // binds selected members of a module into the caller's scope
+    // from("mathlib", "add", "sub as minus")
+    func_nargs_ge!(args, 2);
+    let module = match &args[0] {
+        Block::Mod(m) => m.clone(),
+        Block::Object(Token::Text(_)) => match _import(ns, &vec![args[0].clone()])? {
+            Block::Mod(m) => m,
+            _ => import_error!("Unable to import module")
+        },
+        _ => eval_error!("Unsupported argument to from")
+    };
+
+    let mut selected: Vec<(String, Block)> = Vec::new();
+    for a in &args[1..] {
+        let spec = match a {
+            Block::Object(Token::Text(t)) => t,
+            _ => eval_error!("member names should be text")
+        };
+        let parts: Vec<&str> = spec.split_whitespace().collect();
+        let (name, alias) = match parts.as_slice() {
+            [name] => (*name, *name),
+            [name, "as", alias] => (*name, *alias),
+            _ => eval_error!(format!("invalid import name '{}'", spec))
+        };
+        if !module.is_public(name) {
+            import_error!(format!("cannot import private member '{}'", name))
+        }
+        match module.vars.get(name) {
+            Some(v) => selected.push((alias.to_string(), v.clone())),
+            None => import_error!(format!("'{}' not found in module", name))
+        }
+    }
+    // bind only after all names are found, so a failed import leaves the scope untouched
+    ns.module.vars.extend(selected);
+    Ok(Block::Null)
+}
+
 // ================ iter ================
 
 fn _len(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
     func_nargs_eq!(args, 1);
     let length = match &args[0] {
         Block::List(l) => l.len(),
-        Block::Object(Token::Text(t)) => t.len(),
+        Block::Object(Token::Text(t)) => t.chars().count(), // characters, to agree with indexing
         _ => eval_error!("len() not supported")
     };
     Ok(Block::Object(Token::Number(length as f64)))
@@ -166,7 +367,7 @@ fn _vars(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType
         Ok(Block::List(vars))
     }
     else if let Block::Mod(m) = &args[0] {
-        for (k,_) in &m.vars {
+        for k in m.public_vars().keys() {
             vars.push(Block::Object(Token::Text(k.clone())));
         }
         Ok(Block::List(vars))
@@ -230,16 +431,35 @@ pub fn load_builtins(env_native: &mut ModuleVars) {
     env_native.insert("false".to_string(), Block::Bool(false));
 
     helper::load_func(env_native, "print", _print);
+    helper::load_func(env_native, "eprint", _eprint);
+    helper::load_func(env_native, "input", _input);
     helper::load_func(env_native, "type", _type);
+    helper::load_func(env_native, "num", _num);
+    helper::load_func(env_native, "str", _str);
+    helper::load_func(env_native, "bool", _bool);
+    helper::load_func(env_native, "list", _list);
+    helper::load_func(env_native, "is_number", _is_number);
+    helper::load_func(env_native, "is_text", _is_text);
+    helper::load_func(env_native, "is_callable", _is_callable);
+    _load_types(env_native);
     helper::load_func(env_native, "if", _if);
     helper::load_func(env_native, "len", _len);
     helper::load_func(env_native, "foreach", _foreach);
     helper::load_func(env_native, "try", _try);
     helper::load_func(env_native, "vars", _vars);
+    list::load_list(env_native);
+    string::load_string(env_native);
+    math::load_math(env_native);
+    json::load_json(env_native);
+    random::load_random(env_native);
+    re::load_re(env_native);
 
     helper::load_func(env_native, "import", _import);
+    helper::load_func(env_native, "reload", _reload);
+    helper::load_func(env_native, "search_path", _search_path);
     helper::load_func(env_native, "import_native", _import_native);
     helper::load_func(env_native, "spill", _spill);
+    helper::load_func(env_native, "from", _from);
 
     helper::load_func(env_native, "assert", _assert);
     helper::load_func(env_native, "exit", _exit);
@@ -0,0 +1,336 @@
+/* this file converts between krusty values and rust types for native functions
+*
+* `FromKrusty` extracts a rust value from an argument and `IntoKrusty` turns a rust value into a `Block`.
+* Plain rust functions can then be registered with `helper::load_typed`, which checks the argument count
+* and types before calling them
+*
+* fn repeat(t: String, n: i64) -> Result<Vec<String>, KrustyErrorType> { ... }
+* helper::load_typed(m_vars, "repeat", repeat);
+*
+* Maps are represented by data modules, whose vars are keyed by name
+*
+*/
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::syntax::lexer::Token;
+use crate::syntax::parser::Block;
+use crate::lib::errors::{Error, KrustyErrorType};
+use crate::lib::funcdef::NativeFuncDef;
+use crate::lib::moddef::{Module, MOD_TYPE_VAR};
+
+
+// the builtin type names, `types.Number` etc.
+pub const BUILTIN_TYPES: [&str; 8] = ["Null", "Bool", "Number", "Text", "List", "Function", "Module", "Type"];
+
+// the value returned by `type()`, never equal to a Text
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum TypeValue {
+    Builtin(&'static str),
+    // named by a module's `__type__`, so it can't pass for a builtin type of the same name
+    Custom(String),
+}
+
+impl fmt::Display for TypeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeValue::Builtin(t) => write!(f, "{}", t),
+            TypeValue::Custom(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+pub fn type_name(b: &Block) -> &'static str {
+    match b {
+        Block::Null => "Null",
+        Block::Bool(_) => "Bool",
+        Block::Object(Token::Number(_)) => "Number",
+        Block::Object(Token::Text(_)) => "Text",
+        Block::List(_) => "List",
+        Block::Func(_) | Block::NativeFunc(_) => "Function",
+        Block::Mod(_) => "Module",
+        Block::Type(_) => "Type",
+        _ => "Unknown",
+    }
+}
+
+pub fn type_of(b: &Block) -> TypeValue {
+    match b {
+        // modules used as objects name their own type, data modules have no reserved names
+        Block::Mod(m) if !m.data => match m.vars.get(MOD_TYPE_VAR) {
+            Some(Block::Object(Token::Text(t))) => TypeValue::Custom(t.clone()),
+            _ => TypeValue::Builtin(type_name(b)),
+        },
+        _ => TypeValue::Builtin(type_name(b)),
+    }
+}
+
+pub(crate) fn new_number(n: f64) -> Block {
+    Block::Object(Token::Number(n))
+}
+
+pub(crate) fn new_text<T: Into<String>>(t: T) -> Block {
+    Block::Object(Token::Text(t.into()))
+}
+
+pub(crate) fn list_arg(func: &str, args: &[Block], idx: usize) -> Result<Vec<Block>, KrustyErrorType> {
+    // a copy of the list, native functions return new lists instead of changing their arguments
+    match &args[idx] {
+        Block::List(l) => Ok(l.clone()),
+        b => eval_error!(format!("{}() argument {}: expected List, found {}", func, idx + 1, type_name(b)))
+    }
+}
+
+fn mismatch<T>(expected: &str, found: &Block) -> Result<T, KrustyErrorType> {
+    eval_error!(format!("expected {}, found {}", expected, type_name(found)))
+}
+
+
+pub trait FromKrusty: Sized {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType>;
+}
+
+pub trait IntoKrusty {
+    fn into_krusty(self) -> Block;
+}
+
+
+// ================ FromKrusty =======================
+
+impl FromKrusty for Block {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        Ok(b.clone())
+    }
+}
+
+impl FromKrusty for f64 {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        match b {
+            Block::Object(Token::Number(n)) => Ok(*n),
+            _ => mismatch("Number", b)
+        }
+    }
+}
+
+impl FromKrusty for i64 {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        match b {
+            Block::Object(Token::Number(n)) if n.fract() == 0.0 && n.abs() <= i64::MAX as f64 => Ok(*n as i64),
+            Block::Object(Token::Number(n)) => eval_error!(format!("expected integer, found {}", n)),
+            _ => mismatch("Number", b)
+        }
+    }
+}
+
+impl FromKrusty for usize {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        match i64::from_krusty(b)? {
+            n if n >= 0 => Ok(n as usize),
+            n => eval_error!(format!("expected non-negative integer, found {}", n))
+        }
+    }
+}
+
+impl FromKrusty for String {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        match b {
+            Block::Object(Token::Text(t)) => Ok(t.clone()),
+            _ => mismatch("Text", b)
+        }
+    }
+}
+
+impl FromKrusty for bool {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        match b {
+            Block::Bool(v) => Ok(*v),
+            _ => mismatch("Bool", b)
+        }
+    }
+}
+
+impl<T: FromKrusty> FromKrusty for Vec<T> {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        match b {
+            Block::List(l) => {
+                let mut out = Vec::with_capacity(l.len());
+                for (i, item) in l.iter().enumerate() {
+                    match T::from_krusty(item) {
+                        Ok(v) => out.push(v),
+                        Err(e) => eval_error!(format!("list item {}: {}", i, e.msg()))
+                    }
+                }
+                Ok(out)
+            },
+            _ => mismatch("List", b)
+        }
+    }
+}
+
+impl<T: FromKrusty> FromKrusty for Option<T> {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        match b {
+            Block::Null => Ok(None),
+            _ => Ok(Some(T::from_krusty(b)?))
+        }
+    }
+}
+
+fn map_entries<T: FromKrusty>(b: &Block) -> Result<Vec<(String, T)>, KrustyErrorType> {
+    match b {
+        Block::Mod(m) => {
+            // every member of a data module is public, script modules keep their private names
+            let mut out = Vec::with_capacity(m.vars.len());
+            for (k, v) in m.public_vars() {
+                match T::from_krusty(&v) {
+                    Ok(v) => out.push((k, v)),
+                    Err(e) => eval_error!(format!("member '{}': {}", k, e.msg()))
+                }
+            }
+            Ok(out)
+        },
+        _ => mismatch("Module", b)
+    }
+}
+
+impl<T: FromKrusty> FromKrusty for HashMap<String, T> {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        Ok(map_entries(b)?.into_iter().collect())
+    }
+}
+
+impl<T: FromKrusty> FromKrusty for BTreeMap<String, T> {
+    fn from_krusty(b: &Block) -> Result<Self, KrustyErrorType> {
+        Ok(map_entries(b)?.into_iter().collect())
+    }
+}
+
+
+// ================ IntoKrusty =======================
+
+impl IntoKrusty for Block {
+    fn into_krusty(self) -> Block {
+        self
+    }
+}
+
+impl IntoKrusty for () {
+    fn into_krusty(self) -> Block {
+        Block::Null
+    }
+}
+
+impl IntoKrusty for f64 {
+    fn into_krusty(self) -> Block {
+        Block::Object(Token::Number(self))
+    }
+}
+
+impl IntoKrusty for i64 {
+    fn into_krusty(self) -> Block {
+        Block::Object(Token::Number(self as f64))
+    }
+}
+
+impl IntoKrusty for usize {
+    fn into_krusty(self) -> Block {
+        Block::Object(Token::Number(self as f64))
+    }
+}
+
+impl IntoKrusty for String {
+    fn into_krusty(self) -> Block {
+        Block::Object(Token::Text(self))
+    }
+}
+
+impl IntoKrusty for &str {
+    fn into_krusty(self) -> Block {
+        Block::Object(Token::Text(self.to_string()))
+    }
+}
+
+impl IntoKrusty for bool {
+    fn into_krusty(self) -> Block {
+        Block::Bool(self)
+    }
+}
+
+impl<T: IntoKrusty> IntoKrusty for Vec<T> {
+    fn into_krusty(self) -> Block {
+        Block::List(self.into_iter().map(|v| v.into_krusty()).collect())
+    }
+}
+
+impl<T: IntoKrusty> IntoKrusty for Option<T> {
+    fn into_krusty(self) -> Block {
+        match self {
+            Some(v) => v.into_krusty(),
+            None => Block::Null,
+        }
+    }
+}
+
+impl<T: IntoKrusty> IntoKrusty for HashMap<String, T> {
+    fn into_krusty(self) -> Block {
+        let mut m = Module::new_data();
+        m.vars.extend(self.into_iter().map(|(k, v)| (k, v.into_krusty())));
+        Block::Mod(m)
+    }
+}
+
+impl<T: IntoKrusty> IntoKrusty for BTreeMap<String, T> {
+    fn into_krusty(self) -> Block {
+        let mut m = Module::new_data();
+        m.vars.extend(self.into_iter().map(|(k, v)| (k, v.into_krusty())));
+        Block::Mod(m)
+    }
+}
+
+
+// ================ typed native functions =======================
+
+pub fn arg<T: FromKrusty>(func: &str, args: &[Block], idx: usize) -> Result<T, KrustyErrorType> {
+    match T::from_krusty(&args[idx]) {
+        Ok(v) => Ok(v),
+        Err(e) => eval_error!(format!("{}() argument {}: {}", func, idx + 1, e.msg()))
+    }
+}
+
+
+// implemented for rust functions taking `FromKrusty` arguments and returning `Result<impl IntoKrusty, _>`
+// `Args` only tells the implementations for different arities apart
+pub trait TypedNativeFunc<Args> {
+    fn into_native(self, name: &str) -> NativeFuncDef;
+}
+
+macro_rules! impl_typed_native_func {
+    ($count:expr; $($arg:ident $idx:tt),*) => {
+        impl<Func, Ret, $($arg),*> TypedNativeFunc<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Result<Ret, KrustyErrorType> + 'static,
+            Ret: IntoKrusty,
+            $($arg: FromKrusty,)*
+        {
+            #[allow(unused_variables)]
+            fn into_native(self, name: &str) -> NativeFuncDef {
+                let fname = name.to_string();
+                NativeFuncDef::from_closure(move |_ns, args| {
+                    if args.len() != $count {
+                        eval_error!(format!("{}() expected {}, but received {} args", fname, $count, args.len()))
+                    }
+                    Ok((self)($(arg::<$arg>(&fname, args, $idx)?),*)?.into_krusty())
+                }, name)
+            }
+        }
+    };
+}
+
+impl_typed_native_func!(0; );
+impl_typed_native_func!(1; A 0);
+impl_typed_native_func!(2; A 0, B 1);
+impl_typed_native_func!(3; A 0, B 1, C 2);
+impl_typed_native_func!(4; A 0, B 1, C 2, D 3);
+impl_typed_native_func!(5; A 0, B 1, C 2, D 3, E 4);
+impl_typed_native_func!(6; A 0, B 1, C 2, D 3, E 4, F 5);
@@ -24,6 +24,8 @@ pub enum Error {
 	ParserError{msg: String, fname: String, lino: i32},
 	EvalError{msg: String, fname: String, lino: i32},
 	ImportError{msg: String, fname: String, lino: i32},
+	LimitError{msg: String, fname: String, lino: i32},
+	KeyboardInterrupt{msg: String, fname: String, lino: i32},
 	SysExit{msg: String, fname: String, lino: i32},
 }
 
@@ -36,6 +38,8 @@ impl KrustyError for Error {
 			Error::ParserError{..} => "ParserError".to_string(),
 			Error::EvalError{..} => "EvalError".to_string(),
 			Error::ImportError{..} => "ImportError".to_string(),
+			Error::LimitError{..} => "LimitError".to_string(),
+			Error::KeyboardInterrupt{..} => "KeyboardInterrupt".to_string(),
 			Error::SysExit{..} => "SysExit".to_string(),
 		}
 	}
@@ -47,6 +51,8 @@ impl KrustyError for Error {
 			Error::ParserError{msg, ..} => msg,
 			Error::EvalError{msg, ..} => msg,
 			Error::ImportError{msg, ..} => msg,
+			Error::LimitError{msg, ..} => msg,
+			Error::KeyboardInterrupt{msg, ..} => msg,
 			Error::SysExit{msg, ..} => msg,
 		}
 	}
@@ -0,0 +1,436 @@
+/* this file implements the stable C plugin ABI described in `include/krusty_plugin.h`
+*
+* A C ABI plugin exports two symbols
+*
+*   uint32_t krusty_plugin_abi_version(void);    // KRUSTY_ABI_VERSION the plugin was built against
+*   int krusty_plugin_init(const KrustyApi *api, KrustyModule *module);    // 0 on success
+*
+* Values only cross the boundary as opaque `KrustyValue` handles. Plugins create and inspect
+* them through the `KrustyApi` function table, so they don't depend on the layout of `Block`
+* or on the rustc version used to build the interpreter.
+*
+* The table is append only. New functions are added at the end and `KRUSTY_ABI_VERSION` is bumped
+* only when an existing entry changes.
+*
+*/
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_double, c_int, c_void};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+use libloading;
+
+use crate::syntax::evaluator::NameSpace;
+use crate::syntax::lexer::Token;
+use crate::syntax::parser::Block;
+use crate::lib::errors::{Error, KrustyErrorType};
+use crate::lib::funcdef::NativeFuncDef;
+use crate::lib::moddef::ModuleVars;
+
+pub const KRUSTY_ABI_VERSION: u32 = 1;
+
+pub const ABI_VERSION_SYMBOL: &[u8] = b"krusty_plugin_abi_version";
+pub const INIT_SYMBOL: &[u8] = b"krusty_plugin_init";
+
+// value types returned by `KrustyApi::type_of`
+pub const KRUSTY_TYPE_NULL: c_int = 0;
+pub const KRUSTY_TYPE_BOOL: c_int = 1;
+pub const KRUSTY_TYPE_NUMBER: c_int = 2;
+pub const KRUSTY_TYPE_TEXT: c_int = 3;
+pub const KRUSTY_TYPE_LIST: c_int = 4;
+pub const KRUSTY_TYPE_FUNC: c_int = 5;
+pub const KRUSTY_TYPE_MODULE: c_int = 6;
+pub const KRUSTY_TYPE_OTHER: c_int = 7;
+
+
+// opaque to plugins
+pub struct KrustyValue {
+    block: Block,
+    text: RefCell<Option<CString>>, // NUL terminated copy handed out by as_text
+}
+
+impl KrustyValue {
+    fn into_raw(block: Block) -> *mut KrustyValue {
+        Box::into_raw(Box::new(KrustyValue{ block, text: RefCell::new(None) }))
+    }
+}
+
+// opaque to plugins. Context of a single native function call
+pub struct KrustyCall {
+    ns: *mut c_void, // &mut NameSpace for the duration of the call
+    error: Option<String>,
+}
+
+// opaque to plugins. Vars of the module being loaded
+pub struct KrustyModule {
+    vars: *mut ModuleVars,
+}
+
+
+pub type KrustyNativeFn = unsafe extern "C" fn(
+    call: *mut KrustyCall,
+    argc: usize,
+    argv: *const *const KrustyValue,
+    userdata: *mut c_void) -> *mut KrustyValue;
+
+pub type KrustyInitFn = unsafe extern "C" fn(api: *const KrustyApi, module: *mut KrustyModule) -> c_int;
+pub type KrustyAbiVersionFn = unsafe extern "C" fn() -> u32;
+
+
+#[repr(C)]
+pub struct KrustyApi {
+    pub abi_version: u32,
+    pub size: u32, // size of this table in bytes
+
+    pub new_null: unsafe extern "C" fn() -> *mut KrustyValue,
+    pub new_bool: unsafe extern "C" fn(c_int) -> *mut KrustyValue,
+    pub new_number: unsafe extern "C" fn(c_double) -> *mut KrustyValue,
+    pub new_text: unsafe extern "C" fn(*const c_char, usize) -> *mut KrustyValue,
+    pub new_list: unsafe extern "C" fn() -> *mut KrustyValue,
+    pub list_push: unsafe extern "C" fn(*mut KrustyValue, *mut KrustyValue) -> c_int,
+    pub list_len: unsafe extern "C" fn(*const KrustyValue) -> usize,
+    pub list_get: unsafe extern "C" fn(*const KrustyValue, usize) -> *mut KrustyValue,
+    pub clone: unsafe extern "C" fn(*const KrustyValue) -> *mut KrustyValue,
+    pub free: unsafe extern "C" fn(*mut KrustyValue),
+    pub type_of: unsafe extern "C" fn(*const KrustyValue) -> c_int,
+    pub as_number: unsafe extern "C" fn(*const KrustyValue, *mut c_int) -> c_double,
+    pub as_bool: unsafe extern "C" fn(*const KrustyValue, *mut c_int) -> c_int,
+    pub as_text: unsafe extern "C" fn(*const KrustyValue, *mut usize) -> *const c_char,
+    pub raise_error: unsafe extern "C" fn(*mut KrustyCall, *const c_char),
+    pub call: unsafe extern "C" fn(*mut KrustyCall, *const KrustyValue, usize, *const *const KrustyValue) -> *mut KrustyValue,
+    pub set_function: unsafe extern "C" fn(*mut KrustyModule, *const c_char, KrustyNativeFn, *mut c_void) -> c_int,
+    pub set_value: unsafe extern "C" fn(*mut KrustyModule, *const c_char, *mut KrustyValue) -> c_int,
+}
+
+
+// ================ api implementation ================
+
+unsafe fn take_value(v: *mut KrustyValue) -> Option<Block> {
+    if v.is_null() {
+        None
+    } else {
+        Some(Box::from_raw(v).block)
+    }
+}
+
+unsafe fn borrow_value<'v>(v: *const KrustyValue) -> Option<&'v Block> {
+    v.as_ref().map(|v| &v.block)
+}
+
+unsafe fn c_str_to_string(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(s).to_string_lossy().to_string())
+    }
+}
+
+unsafe fn set_ok(ok: *mut c_int, val: bool) {
+    if !ok.is_null() {
+        *ok = val as c_int;
+    }
+}
+
+unsafe extern "C" fn api_new_null() -> *mut KrustyValue {
+    KrustyValue::into_raw(Block::Null)
+}
+
+unsafe extern "C" fn api_new_bool(b: c_int) -> *mut KrustyValue {
+    KrustyValue::into_raw(Block::Bool(b != 0))
+}
+
+unsafe extern "C" fn api_new_number(n: c_double) -> *mut KrustyValue {
+    KrustyValue::into_raw(Block::Object(Token::Number(n)))
+}
+
+unsafe extern "C" fn api_new_text(s: *const c_char, len: usize) -> *mut KrustyValue {
+    if s.is_null() {
+        return ptr::null_mut()
+    }
+    let bytes = slice::from_raw_parts(s as *const u8, len);
+    KrustyValue::into_raw(Block::Object(Token::Text(String::from_utf8_lossy(bytes).to_string())))
+}
+
+unsafe extern "C" fn api_new_list() -> *mut KrustyValue {
+    KrustyValue::into_raw(Block::List(vec![]))
+}
+
+unsafe extern "C" fn api_list_push(list: *mut KrustyValue, item: *mut KrustyValue) -> c_int {
+    // takes ownership of item, even on failure
+    let item = match take_value(item) {
+        Some(i) => i,
+        None => return 0,
+    };
+    match list.as_mut() {
+        Some(l) => l.block.push_list(item).is_ok() as c_int,
+        None => 0,
+    }
+}
+
+unsafe extern "C" fn api_list_len(list: *const KrustyValue) -> usize {
+    match borrow_value(list) {
+        Some(Block::List(l)) => l.len(),
+        _ => 0,
+    }
+}
+
+unsafe extern "C" fn api_list_get(list: *const KrustyValue, idx: usize) -> *mut KrustyValue {
+    match borrow_value(list) {
+        Some(Block::List(l)) if idx < l.len() => KrustyValue::into_raw(l[idx].clone()),
+        _ => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn api_clone(v: *const KrustyValue) -> *mut KrustyValue {
+    match borrow_value(v) {
+        Some(b) => KrustyValue::into_raw(b.clone()),
+        None => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn api_free(v: *mut KrustyValue) {
+    take_value(v);
+}
+
+unsafe extern "C" fn api_type_of(v: *const KrustyValue) -> c_int {
+    match borrow_value(v) {
+        Some(Block::Null) | None => KRUSTY_TYPE_NULL,
+        Some(Block::Bool(_)) => KRUSTY_TYPE_BOOL,
+        Some(Block::Object(Token::Number(_))) => KRUSTY_TYPE_NUMBER,
+        Some(Block::Object(Token::Text(_))) => KRUSTY_TYPE_TEXT,
+        Some(Block::List(_)) => KRUSTY_TYPE_LIST,
+        Some(Block::Func(_)) | Some(Block::NativeFunc(_)) => KRUSTY_TYPE_FUNC,
+        Some(Block::Mod(_)) => KRUSTY_TYPE_MODULE,
+        Some(_) => KRUSTY_TYPE_OTHER,
+    }
+}
+
+unsafe extern "C" fn api_as_number(v: *const KrustyValue, ok: *mut c_int) -> c_double {
+    match borrow_value(v) {
+        Some(Block::Object(Token::Number(n))) => {
+            set_ok(ok, true);
+            *n
+        },
+        _ => {
+            set_ok(ok, false);
+            0.0
+        }
+    }
+}
+
+unsafe extern "C" fn api_as_bool(v: *const KrustyValue, ok: *mut c_int) -> c_int {
+    match borrow_value(v) {
+        Some(Block::Bool(b)) => {
+            set_ok(ok, true);
+            *b as c_int
+        },
+        _ => {
+            set_ok(ok, false);
+            0
+        }
+    }
+}
+
+unsafe extern "C" fn api_as_text(v: *const KrustyValue, len: *mut usize) -> *const c_char {
+    // the returned string is NUL terminated and lives as long as the value handle
+    let value = match v.as_ref() {
+        Some(v) => v,
+        None => return ptr::null(),
+    };
+    let text = match &value.block {
+        Block::Object(Token::Text(t)) => t,
+        _ => return ptr::null(),
+    };
+    let mut cached = value.text.borrow_mut();
+    let c = cached.get_or_insert_with(|| CString::new(text.replace('\0', "")).unwrap_or_default());
+    // interior NULs are dropped, so the length is taken from the string handed out
+    if !len.is_null() {
+        *len = c.as_bytes().len();
+    }
+    c.as_ptr()
+}
+
+unsafe extern "C" fn api_raise_error(call: *mut KrustyCall, msg: *const c_char) {
+    if let Some(c) = call.as_mut() {
+        c.error = Some(c_str_to_string(msg).unwrap_or("native function error".to_string()));
+    }
+}
+
+unsafe extern "C" fn api_call(
+    call: *mut KrustyCall,
+    func: *const KrustyValue,
+    argc: usize,
+    argv: *const *const KrustyValue) -> *mut KrustyValue {
+
+    let c = match call.as_mut() {
+        Some(c) => c,
+        None => return ptr::null_mut(),
+    };
+    let func = match borrow_value(func) {
+        Some(f) => f.clone(),
+        None => {
+            c.error = Some("call: function is null".to_string());
+            return ptr::null_mut()
+        }
+    };
+    let args = match args_from_raw(argc, argv) {
+        Some(a) => a,
+        None => {
+            c.error = Some("call: argument is null".to_string());
+            return ptr::null_mut()
+        }
+    };
+    let ns = &mut *(c.ns as *mut NameSpace);
+    match ns.eval_func_obj(&func, &Block::List(args), None) {
+        Ok(b) => KrustyValue::into_raw(b),
+        Err(e) => {
+            c.error = Some(e.msg().clone());
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe extern "C" fn api_set_function(
+    module: *mut KrustyModule,
+    name: *const c_char,
+    func: KrustyNativeFn,
+    userdata: *mut c_void) -> c_int {
+
+    let (vars, name) = match (module.as_mut().and_then(|m| m.vars.as_mut()), c_str_to_string(name)) {
+        (Some(v), Some(n)) => (v, n),
+        _ => return 0,
+    };
+    let foreign = ForeignFunc{ func, userdata };
+    vars.insert(name.clone(), Block::NativeFunc(NativeFuncDef::new_foreign(foreign, &name)));
+    1
+}
+
+unsafe extern "C" fn api_set_value(module: *mut KrustyModule, name: *const c_char, value: *mut KrustyValue) -> c_int {
+    // takes ownership of value, even on failure
+    let value = match take_value(value) {
+        Some(v) => v,
+        None => return 0,
+    };
+    match (module.as_mut().and_then(|m| m.vars.as_mut()), c_str_to_string(name)) {
+        (Some(vars), Some(n)) => {
+            vars.insert(n, value);
+            1
+        },
+        _ => 0,
+    }
+}
+
+
+static API: KrustyApi = KrustyApi {
+    abi_version: KRUSTY_ABI_VERSION,
+    size: std::mem::size_of::<KrustyApi>() as u32,
+    new_null: api_new_null,
+    new_bool: api_new_bool,
+    new_number: api_new_number,
+    new_text: api_new_text,
+    new_list: api_new_list,
+    list_push: api_list_push,
+    list_len: api_list_len,
+    list_get: api_list_get,
+    clone: api_clone,
+    free: api_free,
+    type_of: api_type_of,
+    as_number: api_as_number,
+    as_bool: api_as_bool,
+    as_text: api_as_text,
+    raise_error: api_raise_error,
+    call: api_call,
+    set_function: api_set_function,
+    set_value: api_set_value,
+};
+
+
+unsafe fn args_from_raw(argc: usize, argv: *const *const KrustyValue) -> Option<Vec<Block>> {
+    if argc == 0 {
+        return Some(vec![])
+    }
+    if argv.is_null() {
+        return None
+    }
+    slice::from_raw_parts(argv, argc).iter()
+        .map(|v| borrow_value(*v).cloned())
+        .collect()
+}
+
+
+// ================ foreign functions ================
+
+#[derive(Clone, Copy)]
+pub struct ForeignFunc {
+    func: KrustyNativeFn,
+    userdata: *mut c_void, // owned by the plugin, which is never unloaded
+}
+
+impl PartialEq for ForeignFunc {
+    fn eq(&self, other: &Self) -> bool {
+        // plugins may share one C function between entries, told apart by their userdata
+        self.func as usize == other.func as usize && self.userdata == other.userdata
+    }
+}
+
+impl ForeignFunc {
+    pub fn call(&self, ns: &mut NameSpace, args: &[Block]) -> Result<Block, KrustyErrorType> {
+        let handles: Vec<*mut KrustyValue> = args.iter().map(|a| KrustyValue::into_raw(a.clone())).collect();
+        let argv: Vec<*const KrustyValue> = handles.iter().map(|h| *h as *const KrustyValue).collect();
+        let mut call = KrustyCall {
+            ns: ns as *mut NameSpace as *mut c_void,
+            error: None,
+        };
+
+        let res = unsafe {
+            let res = (self.func)(&mut call, argv.len(), argv.as_ptr(), self.userdata);
+            for h in handles {
+                take_value(h);
+            }
+            take_value(res)
+        };
+
+        match (call.error, res) {
+            (Some(msg), _) => eval_error!(msg),
+            (None, Some(b)) => Ok(b),
+            (None, None) => Ok(Block::Null),
+        }
+    }
+}
+
+
+// ================ loading ================
+
+pub fn is_c_plugin(lib: &libloading::Library) -> bool {
+    unsafe { lib.get::<KrustyInitFn>(INIT_SYMBOL).is_ok() }
+}
+
+pub fn load_c_plugin(lib: &libloading::Library, path: &Path, vars: &mut ModuleVars) -> Result<(), KrustyErrorType> {
+    // checks the ABI version the plugin was built against before handing it the api table
+    unsafe {
+        let version = match lib.get::<KrustyAbiVersionFn>(ABI_VERSION_SYMBOL) {
+            Ok(f) => f(),
+            Err(_) => import_error!(format!("{:?}: missing symbol 'krusty_plugin_abi_version'", path))
+        };
+        if version != KRUSTY_ABI_VERSION {
+            import_error!(format!(
+                "{:?}: plugin ABI version {} is not supported (expected {})",
+                path, version, KRUSTY_ABI_VERSION
+            ))
+        }
+
+        let init = match lib.get::<KrustyInitFn>(INIT_SYMBOL) {
+            Ok(f) => f,
+            Err(e) => import_error!(format!("{:?}: {}", path, e))
+        };
+        let mut module = KrustyModule{ vars: vars as *mut ModuleVars };
+        let status = init(&API, &mut module);
+        if status != 0 {
+            import_error!(format!("{:?}: plugin init failed with status {}", path, status))
+        }
+    }
+    Ok(())
+}
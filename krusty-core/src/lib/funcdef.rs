@@ -1,6 +1,9 @@
+use std::rc::Rc;
+
 use crate::syntax::evaluator::NameSpace;
 use crate::syntax::parser::Block;
 use super::errors::KrustyErrorType;
+use super::ffi::ForeignFunc;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct FuncDef {
@@ -10,20 +13,52 @@ pub struct FuncDef {
 
 
 pub type NativeFuncType = fn(&mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType>;
+// native functions that carry captured rust state
+pub type NativeClosureType = Rc<dyn Fn(&mut NameSpace, &Vec<Block>) -> Result<Block, KrustyErrorType>>;
+
+#[derive(Clone)]
+pub enum NativeFuncKind {
+    Rust(NativeFuncType),
+    Closure(NativeClosureType),
+    Foreign(ForeignFunc), // registered through the C plugin ABI
+}
 
 #[derive(Clone)]
 pub struct NativeFuncDef {
-    pub func: NativeFuncType,
+    pub func: NativeFuncKind,
     pub name: String
 }
 
 impl NativeFuncDef {
     pub fn new(func:NativeFuncType, name: &str) -> NativeFuncDef {
         NativeFuncDef {
-            func,
+            func: NativeFuncKind::Rust(func),
+            name:name.to_string()
+        }
+    }
+
+    pub fn from_closure<F>(func: F, name: &str) -> NativeFuncDef
+        where F: Fn(&mut NameSpace, &Vec<Block>) -> Result<Block, KrustyErrorType> + 'static {
+        NativeFuncDef {
+            func: NativeFuncKind::Closure(Rc::new(func)),
             name:name.to_string()
         }
     }
+
+    pub fn new_foreign(func: ForeignFunc, name: &str) -> NativeFuncDef {
+        NativeFuncDef {
+            func: NativeFuncKind::Foreign(func),
+            name:name.to_string()
+        }
+    }
+
+    pub fn call(&self, ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+        match &self.func {
+            NativeFuncKind::Rust(f) => f(ns, args),
+            NativeFuncKind::Closure(f) => f(ns, args),
+            NativeFuncKind::Foreign(f) => f.call(ns, args),
+        }
+    }
 }
 
 impl std::fmt::Debug for NativeFuncDef {
@@ -36,7 +71,15 @@ impl std::fmt::Debug for NativeFuncDef {
 
 impl PartialEq for NativeFuncDef {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+        // identity, not just the same name: every generator has its own `random` closure.
+        // A rust fn can have a different address in each codegen unit, so the same fn registered
+        // from two crates may compare unequal, but two different functions never compare equal
+        match (&self.func, &other.func) {
+            (NativeFuncKind::Rust(a), NativeFuncKind::Rust(b)) => self.name == other.name && *a as usize == *b as usize,
+            (NativeFuncKind::Closure(a), NativeFuncKind::Closure(b)) => Rc::ptr_eq(a, b),
+            (NativeFuncKind::Foreign(a), NativeFuncKind::Foreign(b)) => self.name == other.name && a == b,
+            _ => false,
+        }
     }
 }
 impl Eq for NativeFuncDef {}
@@ -44,7 +87,11 @@ impl Eq for NativeFuncDef {}
 
 impl PartialOrd for NativeFuncDef {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.name.partial_cmp(&other.name)
+        // ordered by name, different functions with the same name are unordered
+        match self.name.partial_cmp(&other.name) {
+            Some(std::cmp::Ordering::Equal) if self != other => None,
+            ord => ord,
+        }
     }
 }
 
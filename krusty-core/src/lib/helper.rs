@@ -1,13 +1,17 @@
 /** this file contains helper functions used while defining an external dylib module
 * external modules should expose a function `load_all` with signature defined by `DynLoadSignature`
-* the `load_all` function can internally use `helper::load_func` to provide function pointers
+* the `load_all` function can internally use `helper::load_func` to provide function pointers,
+* or `helper::load_closure` for functions that capture state.
+* `helper::load_typed` registers plain rust functions whose arguments implement `convert::FromKrusty`
 *
 * [#no_mangle]
 * pub fn load_all(&mut helper::ModuleVars);
 *
 */
 
-use crate::lib::{funcdef, moddef};
+use crate::lib::{convert, funcdef, moddef};
+use crate::lib::errors::KrustyErrorType;
+use crate::syntax::evaluator::NameSpace;
 use crate::syntax::parser::Block;
 
 
@@ -29,8 +33,28 @@ macro_rules! func_nargs_le {
     };
 }
 
+#[macro_export]
+macro_rules! func_nargs_ge {
+    ($vector:expr, $count:expr) => {
+        if $vector.len() < $count {
+            eval_error!(format!("expected at least {}, but received {} args", $count, $vector.len()))
+        }
+    };
+}
+
 pub fn load_func(hm: &mut moddef::ModuleVars, name: &str, f: funcdef::NativeFuncType) {
     hm.insert(name.to_string(), Block::NativeFunc(
         funcdef::NativeFuncDef::new(f, name)
     ));
 }
+
+pub fn load_closure<F>(hm: &mut moddef::ModuleVars, name: &str, f: F)
+    where F: Fn(&mut NameSpace, &Vec<Block>) -> Result<Block, KrustyErrorType> + 'static {
+    hm.insert(name.to_string(), Block::NativeFunc(
+        funcdef::NativeFuncDef::from_closure(f, name)
+    ));
+}
+
+pub fn load_typed<Args, F: convert::TypedNativeFunc<Args>>(hm: &mut moddef::ModuleVars, name: &str, f: F) {
+    hm.insert(name.to_string(), Block::NativeFunc(f.into_native(name)));
+}
@@ -0,0 +1,122 @@
+/* this file contains the `json` builtin module
+*
+* JSON objects become data modules keyed by member name, arrays become lists and all numbers are floats.
+* Data modules keep every member, so names like `_id` or `__type__` survive a round trip
+*
+* config = json.parse('{"name": "krusty", "tags": ["a", "b"]}');
+* text = json.stringify(config, 2);
+*
+*/
+
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+
+use crate::syntax::lexer::Token;
+use crate::syntax::parser::Block;
+use crate::syntax::evaluator::NameSpace;
+
+use super::errors::{Error, KrustyErrorType};
+use super::moddef::{Module, ModuleVars};
+use super::convert::{arg, type_name};
+use super::helper;
+
+
+// the widest indent stringify() accepts
+pub const MAX_INDENT: usize = 16;
+
+fn _to_block(v: Value) -> Block {
+    match v {
+        Value::Null => Block::Null,
+        Value::Bool(b) => Block::Bool(b),
+        Value::Number(n) => Block::Object(Token::Number(n.as_f64().unwrap_or(f64::NAN))),
+        Value::String(s) => Block::Object(Token::Text(s)),
+        Value::Array(a) => Block::List(a.into_iter().map(_to_block).collect()),
+        Value::Object(o) => {
+            let mut m = Module::new_data();
+            m.vars.extend(o.into_iter().map(|(k, v)| (k, _to_block(v))));
+            Block::Mod(m)
+        },
+    }
+}
+
+fn _to_value(b: &Block) -> Result<Value, KrustyErrorType> {
+    Ok(match b {
+        Block::Null => Value::Null,
+        Block::Bool(v) => Value::Bool(*v),
+        Block::Object(Token::Number(n)) => match Number::from_f64(*n) {
+            // whole numbers are written without a fraction
+            Some(_) if n.fract() == 0.0 && n.abs() < 1e15 => Value::Number(Number::from(*n as i64)),
+            Some(num) => Value::Number(num),
+            None => eval_error!(format!("stringify() cannot serialize {}", n))
+        },
+        Block::Object(Token::Text(t)) => Value::String(t.clone()),
+        Block::List(l) => {
+            let mut out = Vec::with_capacity(l.len());
+            for item in l {
+                out.push(_to_value(item)?);
+            }
+            Value::Array(out)
+        },
+        Block::Mod(m) => {
+            // private members of script modules are left out, data modules have none
+            let mut out = Map::new();
+            for (k, v) in m.public_vars() {
+                let value = _to_value(&v)?;
+                out.insert(k, value);
+            }
+            Value::Object(out)
+        },
+        _ => eval_error!(format!("stringify() cannot serialize {}", type_name(b)))
+    })
+}
+
+
+fn _parse(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    let text: String = arg("parse", args, 0)?;
+    match serde_json::from_str::<Value>(&text) {
+        Ok(v) => Ok(_to_block(v)),
+        Err(e) => {
+            // serde_json appends the position to its message, it is reported separately here
+            let msg = e.to_string();
+            let reason = match msg.rfind(" at line ") {
+                Some(i) => &msg[..i],
+                None => &msg,
+            };
+            eval_error!(format!("parse() invalid JSON at line {}, column {}: {}", e.line(), e.column(), reason))
+        }
+    }
+}
+
+fn _stringify(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // stringify(value) is compact, stringify(value, indent) pretty prints with `indent` spaces
+    func_nargs_ge!(args, 1);
+    func_nargs_le!(args, 2);
+    let value = _to_value(&args[0])?;
+    let indent: usize = if args.len() == 2 { arg("stringify", args, 1)? } else { 0 };
+    if indent > MAX_INDENT {
+        eval_error!(format!("stringify() indent {} is larger than {}", indent, MAX_INDENT))
+    }
+
+    let text = if indent == 0 {
+        value.to_string()
+    } else {
+        let spaces = " ".repeat(indent);
+        let mut buf = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(spaces.as_bytes());
+        let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
+        if let Err(e) = value.serialize(&mut ser) {
+            eval_error!(format!("stringify() failed: {}", e))
+        }
+        String::from_utf8_lossy(&buf).into_owned()
+    };
+    Ok(Block::Object(Token::Text(text)))
+}
+
+
+pub fn load_json(env_native: &mut ModuleVars) {
+    let mut m = Module::new(None);
+    helper::load_func(&mut m.vars, "parse", _parse);
+    helper::load_func(&mut m.vars, "stringify", _stringify);
+    env_native.insert("json".to_string(), Block::Mod(m));
+}
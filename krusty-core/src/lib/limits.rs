@@ -0,0 +1,189 @@
+/* this file keeps track of the resources used by a running script
+*
+* `Limits` is the configuration, `Limiter` the counters checked by the evaluator
+* - max_depth: nested krusty function calls
+* - max_steps: expressions evaluated during one top level evaluation
+* - timeout: wall clock time of one top level evaluation
+* - max_memory: approximate bytes held by variables
+*
+* The limiter also polls an `InterruptHandle`, which other threads or signal handlers use to cancel a running script
+*
+*/
+
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::syntax::lexer::Token;
+use crate::syntax::parser::Block;
+use crate::lib::errors::{Error, KrustyErrorType};
+
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+const TIME_CHECK_MASK: u64 = 0xff; // the clock is read every 256 steps
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    pub max_depth: Option<usize>,
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub max_memory: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_steps: None,
+            timeout: None,
+            max_memory: None,
+        }
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Limits {
+        Limits {
+            max_depth: None,
+            ..Limits::default()
+        }
+    }
+}
+
+
+pub fn approx_size(b: &Block) -> usize {
+    // heap and inline bytes of a value, ignoring allocator overhead
+    let inner = match b {
+        Block::Object(Token::Text(t)) => t.len(),
+        Block::List(l) => l.iter().map(approx_size).sum(),
+        Block::Mod(m) => m.vars.iter().map(|(k, v)| k.len() + approx_size(v)).sum(),
+        _ => 0,
+    };
+    mem::size_of::<Block>() + inner
+}
+
+
+// cloneable and Send, so it can be handed to another thread or a Ctrl+C handler
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn new() -> InterruptHandle {
+        InterruptHandle::default()
+    }
+
+    pub fn interrupt(&self) {
+        // the running script stops with a KeyboardInterrupt at its next step
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    pub fn clear(&self) {
+        self.flag.store(false, Ordering::SeqCst);
+    }
+
+    fn take(&self) -> bool {
+        self.is_interrupted() && self.flag.swap(false, Ordering::SeqCst)
+    }
+}
+
+
+#[derive(Debug, Default)]
+pub struct Limiter {
+    limits: Limits,
+    interrupt: InterruptHandle,
+    depth: usize,
+    steps: u64,
+    started: Option<Instant>,
+    memory: usize,
+}
+
+impl Limiter {
+    pub fn new(limits: Limits) -> Limiter {
+        Limiter {
+            limits,
+            ..Limiter::default()
+        }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.interrupt = handle;
+    }
+
+    pub fn begin(&mut self) {
+        // the step budget and timeout apply to each top level evaluation
+        self.steps = 0;
+        self.started = Some(Instant::now());
+    }
+
+    pub fn enter_call(&mut self) -> Result<(), KrustyErrorType> {
+        if let Some(max) = self.limits.max_depth {
+            if self.depth >= max {
+                limit_error!(format!("maximum recursion depth exceeded ({})", max))
+            }
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn exit_call(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    pub fn step(&mut self) -> Result<(), KrustyErrorType> {
+        if self.interrupt.take() {
+            keyboard_interrupt_error!()
+        }
+        self.steps += 1;
+        if let Some(max) = self.limits.max_steps {
+            if self.steps > max {
+                limit_error!(format!("step budget of {} exceeded", max))
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            let started = *self.started.get_or_insert_with(Instant::now);
+            if self.steps & TIME_CHECK_MASK == 0 && started.elapsed() > timeout {
+                limit_error!(format!("timeout: execution took longer than {:?}", timeout))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tracks_memory(&self) -> bool {
+        self.limits.max_memory.is_some()
+    }
+
+    pub fn alloc(&mut self, bytes: usize, replaced: usize) -> Result<(), KrustyErrorType> {
+        let memory = (self.memory + bytes).saturating_sub(replaced);
+        if let Some(max) = self.limits.max_memory {
+            if memory > max {
+                limit_error!(format!("memory limit of {} bytes exceeded", max))
+            }
+        }
+        self.memory = memory;
+        Ok(())
+    }
+
+    pub fn free(&mut self, bytes: usize) {
+        self.memory = self.memory.saturating_sub(bytes);
+    }
+}
@@ -0,0 +1,266 @@
+/* this file contains the list builtins
+*
+* lists are values, so functions like push and remove return a new list instead of changing their argument
+*
+* l = push(l, 4);
+* evens = filter(l, (x) => { ret x % 2 == 0; });
+*
+* callbacks can be krusty functions or native functions
+*
+*/
+
+use std::cmp::Ordering;
+
+use crate::syntax::lexer::Token;
+use crate::syntax::parser::Block;
+use crate::syntax::evaluator::NameSpace;
+
+use super::errors::{Error, KrustyErrorType};
+use super::moddef::ModuleVars;
+use super::convert::{arg, list_arg, new_number, type_name};
+use super::string;
+use super::helper;
+
+
+pub const MAX_RANGE_LEN: usize = 1 << 24;
+
+fn _func_arg<'b>(func: &str, args: &'b [Block], idx: usize) -> Result<&'b Block, KrustyErrorType> {
+    match &args[idx] {
+        f @ (Block::Func(_) | Block::NativeFunc(_)) => Ok(f),
+        b => eval_error!(format!("{}() argument {}: expected Function, found {}", func, idx + 1, type_name(b)))
+    }
+}
+
+fn _call(ns: &mut NameSpace, func: &Block, args: Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // wrapped in a list so that list items are passed as one argument
+    ns.eval_func_obj(func, &Block::List(args), None)
+}
+
+fn _truthy(func: &str, b: Block) -> Result<bool, KrustyErrorType> {
+    match b {
+        Block::Bool(v) => Ok(v),
+        b => eval_error!(format!("{}() callback should return a Bool, found {}", func, type_name(&b)))
+    }
+}
+
+
+// ================ building lists ================
+
+fn _push(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_ge!(args, 2);
+    let mut l = list_arg("push", args, 0)?;
+    l.extend_from_slice(&args[1..]);
+    Ok(Block::List(l))
+}
+
+fn _pop(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // lists can't be changed in place, so both the shorter list and the removed item are returned
+    // rest = pop(l); l = rest[0]; item = rest[1];
+    func_nargs_eq!(args, 1);
+    let mut l = list_arg("pop", args, 0)?;
+    match l.pop() {
+        Some(item) => Ok(Block::List(vec![Block::List(l), item])),
+        None => eval_error!("pop() from empty list")
+    }
+}
+
+fn _insert(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 3);
+    let mut l = list_arg("insert", args, 0)?;
+    let idx: usize = arg("insert", args, 1)?;
+    if idx > l.len() {
+        eval_error!(format!("insert() index {} out of range for list of length {}", idx, l.len()))
+    }
+    l.insert(idx, args[2].clone());
+    Ok(Block::List(l))
+}
+
+fn _remove(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 2);
+    let mut l = list_arg("remove", args, 0)?;
+    let idx: usize = arg("remove", args, 1)?;
+    if idx >= l.len() {
+        eval_error!(format!("remove() index {} out of range for list of length {}", idx, l.len()))
+    }
+    l.remove(idx);
+    Ok(Block::List(l))
+}
+
+fn _reverse(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    let mut l = list_arg("reverse", args, 0)?;
+    l.reverse();
+    Ok(Block::List(l))
+}
+
+fn _range(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // range(end), range(start, end) or range(start, end, step), end is excluded
+    func_nargs_ge!(args, 1);
+    func_nargs_le!(args, 3);
+    let (start, end): (f64, f64) = match args.len() {
+        1 => (0.0, arg("range", args, 0)?),
+        _ => (arg("range", args, 0)?, arg("range", args, 1)?),
+    };
+    let step: f64 = if args.len() == 3 { arg("range", args, 2)? } else { 1.0 };
+    if !start.is_finite() || !end.is_finite() || !step.is_finite() {
+        eval_error!("range() bounds and step must be finite")
+    }
+    if step == 0.0 {
+        eval_error!("range() step must not be zero")
+    }
+
+    // the length is fixed up front, adding step repeatedly stops changing large floats
+    let count = ((end - start) / step).ceil().max(0.0);
+    if count > MAX_RANGE_LEN as f64 {
+        eval_error!(format!("range() of {} items is longer than the limit of {}", count, MAX_RANGE_LEN))
+    }
+    let out = (0..count as usize)
+        .map(|k| Block::Object(Token::Number(start + k as f64 * step)))
+        .collect();
+    Ok(Block::List(out))
+}
+
+fn _zip(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // stops at the end of the shortest list
+    func_nargs_ge!(args, 2);
+    let mut lists: Vec<Vec<Block>> = Vec::new();
+    for i in 0..args.len() {
+        lists.push(list_arg("zip", args, i)?);
+    }
+    let shortest = lists.iter().map(|l| l.len()).min().unwrap_or(0);
+    let out = (0..shortest)
+        .map(|i| Block::List(lists.iter().map(|l| l[i].clone()).collect()))
+        .collect();
+    Ok(Block::List(out))
+}
+
+fn _enumerate(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    let l = list_arg("enumerate", args, 0)?;
+    let out = l.into_iter()
+        .enumerate()
+        .map(|(i, b)| Block::List(vec![new_number(i as f64), b]))
+        .collect();
+    Ok(Block::List(out))
+}
+
+
+// ================ searching ================
+
+fn _contains(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 2);
+    let l = list_arg("contains", args, 0)?;
+    Ok(Block::Bool(l.contains(&args[1])))
+}
+
+fn _index_of(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // -1 when the item is not in the list
+    func_nargs_eq!(args, 2);
+    let l = list_arg("index_of", args, 0)?;
+    let idx = match l.iter().position(|b| b == &args[1]) {
+        Some(i) => i as f64,
+        None => -1.0,
+    };
+    Ok(Block::Object(Token::Number(idx)))
+}
+
+
+// ================ callbacks ================
+
+fn _map(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 2);
+    let l = list_arg("map", args, 0)?;
+    let f = _func_arg("map", args, 1)?;
+    let mut out: Vec<Block> = Vec::with_capacity(l.len());
+    for b in l {
+        out.push(_call(ns, f, vec![b])?);
+    }
+    Ok(Block::List(out))
+}
+
+fn _filter(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 2);
+    let l = list_arg("filter", args, 0)?;
+    let f = _func_arg("filter", args, 1)?;
+    let mut out: Vec<Block> = Vec::new();
+    for b in l {
+        if _truthy("filter", _call(ns, f, vec![b.clone()])?)? {
+            out.push(b);
+        }
+    }
+    Ok(Block::List(out))
+}
+
+fn _reduce(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // reduce(list, (acc, x) => {...}, initial), the first item is the initial value when it is omitted
+    func_nargs_ge!(args, 2);
+    func_nargs_le!(args, 3);
+    let l = list_arg("reduce", args, 0)?;
+    let f = _func_arg("reduce", args, 1)?;
+    let mut items = l.into_iter();
+    let mut acc = match args.get(2) {
+        Some(init) => init.clone(),
+        None => match items.next() {
+            Some(b) => b,
+            None => eval_error!("reduce() of empty list with no initial value")
+        }
+    };
+    for b in items {
+        acc = _call(ns, f, vec![acc, b])?;
+    }
+    Ok(acc)
+}
+
+fn _sort(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // sort(list) or sort(list, key), items are ordered by the value the key function returns for them
+    func_nargs_ge!(args, 1);
+    func_nargs_le!(args, 2);
+    let l = list_arg("sort", args, 0)?;
+    let keys = match args.get(1) {
+        Some(_) => {
+            let f = _func_arg("sort", args, 1)?;
+            let mut keys = Vec::with_capacity(l.len());
+            for b in l.iter() {
+                keys.push(_call(ns, f, vec![b.clone()])?);
+            }
+            keys
+        },
+        None => l.clone(),
+    };
+
+    for k in keys.iter() {
+        match k {
+            Block::Object(Token::Number(n)) if n.is_nan() => eval_error!("sort() cannot order NaN"),
+            Block::Object(Token::Number(_)) | Block::Object(Token::Text(_)) | Block::Bool(_) => (),
+            _ => eval_error!(format!("sort() cannot order {}", type_name(k)))
+        }
+        if type_name(k) != type_name(&keys[0]) {
+            eval_error!(format!("sort() cannot compare {} with {}", type_name(&keys[0]), type_name(k)))
+        }
+    }
+
+    let mut pairs: Vec<(Block, Block)> = keys.into_iter().zip(l).collect();
+    pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    Ok(Block::List(pairs.into_iter().map(|(_, b)| b).collect()))
+}
+
+
+pub fn load_list(env_native: &mut ModuleVars) {
+    helper::load_func(env_native, "push", _push);
+    helper::load_func(env_native, "pop", _pop);
+    helper::load_func(env_native, "insert", _insert);
+    helper::load_func(env_native, "remove", _remove);
+    helper::load_func(env_native, "reverse", _reverse);
+    helper::load_func(env_native, "range", _range);
+    helper::load_func(env_native, "zip", _zip);
+    helper::load_func(env_native, "enumerate", _enumerate);
+    helper::load_func(env_native, "join", string::_join); // same as string.join
+
+    helper::load_func(env_native, "contains", _contains);
+    helper::load_func(env_native, "index_of", _index_of);
+
+    helper::load_func(env_native, "map", _map);
+    helper::load_func(env_native, "filter", _filter);
+    helper::load_func(env_native, "reduce", _reduce);
+    helper::load_func(env_native, "sort", _sort);
+}
@@ -0,0 +1,278 @@
+/* this file describes the `krusty.toml` package manifest, the `krusty.lock` lockfile
+* and the `index.toml` file that tracks packages installed under `pkg::get_install_path()`
+*
+* [package]
+* name = "mathlib"
+* version = "0.1.0"
+* description = "basic arithmetic"
+* entry = "__pkg__.krt"        # optional, defaults to __pkg__.krt
+* native = ["libfast.so"]      # optional native libs shipped with the package
+*
+* [dependencies]
+* trig = { version = "^1.2", path = "../trig" }   # local path dependency
+* units = "~0.3"                                  # resolved from the local registry
+*
+*/
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use semver::{Version, VersionReq};
+
+use crate::lib::errors::{Error, KrustyErrorType};
+use crate::lib::pkg;
+
+pub const MANIFEST_FILE: &str = "krusty.toml";
+pub const LOCK_FILE: &str = "krusty.lock";
+pub const INDEX_FILE: &str = "index.toml";
+
+const LOCK_FILE_COMMENT: &str = r#"# This file was created by Krusty's --install option
+# - records the exact package versions that were resolved
+
+"#;
+
+const INDEX_FILE_COMMENT: &str = r#"# This file is maintained by Krusty's package manager
+# - records the packages installed in this directory. Do not edit
+
+"#;
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "default_entry")]
+    pub entry: String,
+    #[serde(default)]
+    pub native: Vec<String>,
+}
+
+fn default_entry() -> String {
+    pkg::DIR_PKG_INITIALIZER.to_string()
+}
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    Version(String),
+    Detailed {
+        version: Option<String>,
+        path: Option<String>,
+    },
+}
+
+impl Dependency {
+    pub fn version_req(&self) -> Result<VersionReq, KrustyErrorType> {
+        let req = match self {
+            Dependency::Version(v) => Some(v),
+            Dependency::Detailed{version, ..} => version.as_ref(),
+        };
+        match req {
+            Some(r) => match VersionReq::parse(r) {
+                Ok(req) => Ok(req),
+                Err(e) => generic_error!(format!("invalid version requirement '{}': {}", r, e))
+            },
+            None => Ok(VersionReq::STAR),
+        }
+    }
+
+    pub fn path(&self) -> Option<&String> {
+        match self {
+            Dependency::Version(_) => None,
+            Dependency::Detailed{path, ..} => path.as_ref(),
+        }
+    }
+}
+
+
+fn package_file<'a>(kind: &str, file: &'a str) -> Result<&'a Path, KrustyErrorType> {
+    // files named by a manifest must stay inside the package directory
+    let path = Path::new(file);
+    if !path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        generic_error!(format!("package {} '{}' must be a relative path inside the package", kind, file))
+    }
+    Ok(path)
+}
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Manifest {
+    pub package: PackageInfo,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+
+impl Manifest {
+    pub fn from_dir(dir: &Path) -> Result<Manifest, KrustyErrorType> {
+        let file = dir.join(MANIFEST_FILE);
+        let text = fs::read_to_string(&file)?;
+        match toml::from_str::<Manifest>(&text) {
+            Ok(m) => Ok(m),
+            Err(e) => generic_error!(format!("{}: {}", file.display(), e))
+        }
+    }
+
+    pub fn exists_in(dir: &Path) -> bool {
+        dir.join(MANIFEST_FILE).is_file()
+    }
+
+    pub fn version(&self) -> Result<Version, KrustyErrorType> {
+        match Version::parse(&self.package.version) {
+            Ok(v) => Ok(v),
+            Err(e) => generic_error!(format!("invalid version '{}': {}", self.package.version, e))
+        }
+    }
+
+    pub fn entry_path(&self, dir: &Path) -> Result<PathBuf, KrustyErrorType> {
+        Ok(dir.join(package_file("entry point", &self.package.entry)?))
+    }
+
+    pub fn validate(&self, dir: &Path) -> Result<(), KrustyErrorType> {
+        // checks the manifest against the package directory it was loaded from
+        let name = &self.package.name;
+        let mut chars = name.chars();
+        let valid_name = match chars.next() {
+            Some(c) => (c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+            None => false,
+        };
+        if !valid_name {
+            generic_error!(format!("invalid package name '{}'", name))
+        }
+        self.version()?;
+
+        if !self.entry_path(dir)?.is_file() {
+            generic_error!(format!("package entry point '{}' not found", self.package.entry))
+        }
+        for lib in &self.package.native {
+            if !dir.join(package_file("native lib", lib)?).is_file() {
+                generic_error!(format!("native lib '{}' not found", lib))
+            }
+        }
+        for (dep_name, dep) in &self.dependencies {
+            if let Err(e) = dep.version_req() {
+                generic_error!(format!("dependency '{}': {}", dep_name, e.msg()))
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    pub source: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LockFile {
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+
+impl LockFile {
+    pub fn from_dir(dir: &Path) -> Result<Option<LockFile>, KrustyErrorType> {
+        let file = dir.join(LOCK_FILE);
+        if !file.is_file() {
+            return Ok(None)
+        }
+        let text = fs::read_to_string(&file)?;
+        match toml::from_str::<LockFile>(&text) {
+            Ok(l) => Ok(Some(l)),
+            Err(e) => generic_error!(format!("{}: {}", file.display(), e))
+        }
+    }
+
+    pub fn locked_version(&self, name: &str) -> Option<Version> {
+        self.packages.iter()
+            .find(|p| p.name == name)
+            .and_then(|p| Version::parse(&p.version).ok())
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, KrustyErrorType> {
+        let text = match toml::to_string(self) {
+            Ok(t) => t,
+            Err(e) => generic_error!(format!("unable to write lockfile: {}", e))
+        };
+        let file = dir.join(LOCK_FILE);
+        fs::write(&file, LOCK_FILE_COMMENT.to_string() + &text)?;
+        Ok(file)
+    }
+}
+
+
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct InstalledPackage {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>, // packages installed without a manifest are not versioned
+    pub source: String,
+    pub location: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PackageIndex {
+    #[serde(default, rename = "package")]
+    pub packages: Vec<InstalledPackage>,
+}
+
+
+impl PackageIndex {
+    pub fn from_dir(dir: &Path) -> Result<PackageIndex, KrustyErrorType> {
+        let file = dir.join(INDEX_FILE);
+        if !file.is_file() {
+            return Ok(PackageIndex::default())
+        }
+        let text = fs::read_to_string(&file)?;
+        match toml::from_str::<PackageIndex>(&text) {
+            Ok(i) => Ok(i),
+            Err(e) => generic_error!(format!("{}: {}", file.display(), e))
+        }
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<(), KrustyErrorType> {
+        let text = match toml::to_string(self) {
+            Ok(t) => t,
+            Err(e) => generic_error!(format!("unable to write package index: {}", e))
+        };
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(INDEX_FILE), INDEX_FILE_COMMENT.to_string() + &text)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InstalledPackage> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+
+    pub fn insert(&mut self, pkg: InstalledPackage) {
+        self.remove(&pkg.name);
+        self.packages.push(pkg);
+        self.packages.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<InstalledPackage> {
+        let pos = self.packages.iter().position(|p| p.name == name)?;
+        Some(self.packages.remove(pos))
+    }
+
+    pub fn dependents(&self, name: &str) -> Vec<&InstalledPackage> {
+        self.packages.iter()
+            .filter(|p| p.dependencies.iter().any(|d| d == name))
+            .collect()
+    }
+}
@@ -0,0 +1,223 @@
+/* this file contains the `math` builtin module
+*
+* functions raise an error for arguments outside their domain instead of returning nan,
+* nan and inf arguments are passed through
+*
+* r = math.sqrt(math.pow(3, 2) + math.pow(4, 2));
+*
+*/
+
+use std::f64::consts;
+
+use crate::syntax::lexer::Token;
+use crate::syntax::parser::Block;
+use crate::syntax::evaluator::NameSpace;
+
+use super::errors::{Error, KrustyErrorType};
+use super::moddef::{Module, ModuleVars};
+use super::convert::{arg, new_number, type_name};
+use super::helper;
+
+
+fn _domain_error<T>(func: &str, x: f64, expected: &str) -> Result<T, KrustyErrorType> {
+    eval_error!(format!("{}() math domain error: {} is {}", func, x, expected))
+}
+
+
+// ================ typed functions ================
+
+fn _sqrt(x: f64) -> Result<f64, KrustyErrorType> {
+    if x < 0.0 {
+        return _domain_error("sqrt", x, "negative");
+    }
+    Ok(x.sqrt())
+}
+
+fn _pow(base: f64, exp: f64) -> Result<f64, KrustyErrorType> {
+    if base < 0.0 && exp.is_finite() && exp.fract() != 0.0 {
+        eval_error!(format!("pow() math domain error: negative base {} with fractional exponent {}", base, exp))
+    }
+    if base == 0.0 && exp < 0.0 {
+        eval_error!(format!("pow() math domain error: zero raised to negative exponent {}", exp))
+    }
+    Ok(base.powf(exp))
+}
+
+fn _exp(x: f64) -> Result<f64, KrustyErrorType> {
+    Ok(x.exp())
+}
+
+fn _log10(x: f64) -> Result<f64, KrustyErrorType> {
+    if x <= 0.0 {
+        return _domain_error("log10", x, "not positive");
+    }
+    Ok(x.log10())
+}
+
+fn _log2(x: f64) -> Result<f64, KrustyErrorType> {
+    if x <= 0.0 {
+        return _domain_error("log2", x, "not positive");
+    }
+    Ok(x.log2())
+}
+
+fn _abs(x: f64) -> Result<f64, KrustyErrorType> {
+    Ok(x.abs())
+}
+
+fn _floor(x: f64) -> Result<f64, KrustyErrorType> {
+    Ok(x.floor())
+}
+
+fn _ceil(x: f64) -> Result<f64, KrustyErrorType> {
+    Ok(x.ceil())
+}
+
+fn _sin(x: f64) -> Result<f64, KrustyErrorType> {
+    Ok(x.sin())
+}
+
+fn _cos(x: f64) -> Result<f64, KrustyErrorType> {
+    Ok(x.cos())
+}
+
+fn _tan(x: f64) -> Result<f64, KrustyErrorType> {
+    Ok(x.tan())
+}
+
+fn _asin(x: f64) -> Result<f64, KrustyErrorType> {
+    if x.abs() > 1.0 {
+        return _domain_error("asin", x, "outside -1..1");
+    }
+    Ok(x.asin())
+}
+
+fn _acos(x: f64) -> Result<f64, KrustyErrorType> {
+    if x.abs() > 1.0 {
+        return _domain_error("acos", x, "outside -1..1");
+    }
+    Ok(x.acos())
+}
+
+fn _atan(x: f64) -> Result<f64, KrustyErrorType> {
+    Ok(x.atan())
+}
+
+fn _atan2(y: f64, x: f64) -> Result<f64, KrustyErrorType> {
+    Ok(y.atan2(x))
+}
+
+fn _is_nan(x: f64) -> Result<bool, KrustyErrorType> {
+    Ok(x.is_nan())
+}
+
+fn _is_inf(x: f64) -> Result<bool, KrustyErrorType> {
+    Ok(x.is_infinite())
+}
+
+
+// ================ optional and variadic arguments ================
+
+fn _log(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // natural logarithm, or log(x, base)
+    func_nargs_ge!(args, 1);
+    func_nargs_le!(args, 2);
+    let x: f64 = arg("log", args, 0)?;
+    if x <= 0.0 {
+        return _domain_error("log", x, "not positive");
+    }
+    if args.len() == 1 {
+        return Ok(new_number(x.ln()));
+    }
+    let base: f64 = arg("log", args, 1)?;
+    if base <= 0.0 || base == 1.0 {
+        eval_error!(format!("log() math domain error: invalid base {}", base))
+    }
+    Ok(new_number(x.log(base)))
+}
+
+fn _round(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // round(x) or round(x, digits), halves round away from zero
+    func_nargs_ge!(args, 1);
+    func_nargs_le!(args, 2);
+    let x: f64 = arg("round", args, 0)?;
+    if args.len() == 1 {
+        return Ok(new_number(x.round()));
+    }
+    let digits: i64 = arg("round", args, 1)?;
+    let scale = 10f64.powi(digits.clamp(-308, 308) as i32);
+    Ok(new_number((x * scale).round() / scale))
+}
+
+fn _numbers(func: &str, args: &[Block]) -> Result<Vec<f64>, KrustyErrorType> {
+    // either the arguments themselves or a single list argument
+    let items = match args {
+        [Block::List(l)] => l.as_slice(),
+        _ => args,
+    };
+    if items.is_empty() {
+        eval_error!(format!("{}() of empty sequence", func))
+    }
+    let mut out = Vec::with_capacity(items.len());
+    for b in items {
+        match b {
+            Block::Object(Token::Number(n)) => out.push(*n),
+            _ => eval_error!(format!("{}() expected Number, found {}", func, type_name(b)))
+        }
+    }
+    Ok(out)
+}
+
+fn _min(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // nan if any value is nan
+    let nums = _numbers("min", args)?;
+    if nums.iter().any(|n| n.is_nan()) {
+        return Ok(new_number(f64::NAN));
+    }
+    Ok(new_number(nums.into_iter().fold(f64::INFINITY, f64::min)))
+}
+
+fn _max(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    let nums = _numbers("max", args)?;
+    if nums.iter().any(|n| n.is_nan()) {
+        return Ok(new_number(f64::NAN));
+    }
+    Ok(new_number(nums.into_iter().fold(f64::NEG_INFINITY, f64::max)))
+}
+
+
+pub fn load_math(env_native: &mut ModuleVars) {
+    let mut m = Module::new(None);
+    let vars = &mut m.vars;
+
+    vars.insert("pi".to_string(), new_number(consts::PI));
+    vars.insert("e".to_string(), new_number(consts::E));
+    vars.insert("tau".to_string(), new_number(consts::TAU));
+    vars.insert("inf".to_string(), new_number(f64::INFINITY));
+    vars.insert("nan".to_string(), new_number(f64::NAN));
+
+    helper::load_func(vars, "log", _log);
+    helper::load_func(vars, "round", _round);
+    helper::load_func(vars, "min", _min);
+    helper::load_func(vars, "max", _max);
+
+    helper::load_typed(vars, "sqrt", _sqrt);
+    helper::load_typed(vars, "pow", _pow);
+    helper::load_typed(vars, "exp", _exp);
+    helper::load_typed(vars, "log10", _log10);
+    helper::load_typed(vars, "log2", _log2);
+    helper::load_typed(vars, "abs", _abs);
+    helper::load_typed(vars, "floor", _floor);
+    helper::load_typed(vars, "ceil", _ceil);
+    helper::load_typed(vars, "sin", _sin);
+    helper::load_typed(vars, "cos", _cos);
+    helper::load_typed(vars, "tan", _tan);
+    helper::load_typed(vars, "asin", _asin);
+    helper::load_typed(vars, "acos", _acos);
+    helper::load_typed(vars, "atan", _atan);
+    helper::load_typed(vars, "atan2", _atan2);
+    helper::load_typed(vars, "is_nan", _is_nan);
+    helper::load_typed(vars, "is_inf", _is_inf);
+
+    env_native.insert("math".to_string(), Block::Mod(m));
+}
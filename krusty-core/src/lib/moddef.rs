@@ -1,13 +1,21 @@
+#[cfg(debug_assertions)]
+use std::env; // required for print_verbose! macro
+
 use std::cmp::Ordering;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::collections::HashMap;
 
 use libloading;
 use lazy_static::lazy_static; // 1.4.0
 use std::sync::Mutex;
+use std::ffi::CStr;
+use std::os::raw::c_char;
 
 use crate::syntax::parser::Block;
+use crate::syntax::lexer::Token;
+use crate::lib::errors::{Error, KrustyErrorType};
+use crate::lib::ffi;
 
 
 lazy_static! {
@@ -15,14 +23,48 @@ lazy_static! {
     static ref _DYLIB_REFS: Mutex<HashMap<PathBuf, libloading::Library>> = Mutex::new(HashMap::new());
 }
 
+pub const MOD_EXPORTS_VAR: &str = "__exports__";
+pub const MOD_TYPE_VAR: &str = "__type__";
+
 pub type ModuleVars = HashMap<String, Block>;
 pub type DynLoadSignature = fn(&mut ModuleVars);
+pub type DynMetadataSignature = extern "C" fn() -> *const PluginMetadata;
+
+// rust plugins share `Block` and `NameSpace` layouts with the interpreter,
+// so they must be built against this exact version of krusty-core
+pub const CORE_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+pub const METADATA_SYMBOL: &[u8] = b"krusty_plugin_metadata";
+pub const LOAD_ALL_SYMBOL: &[u8] = b"load_all";
+
+
+// exported by rust plugins through the `plugin_metadata!` macro
+#[repr(C)]
+pub struct PluginMetadata {
+    pub name: *const c_char,
+    pub version: *const c_char,
+    pub core_version: *const c_char,
+}
+
+unsafe impl Sync for PluginMetadata {} // only ever points to static strings
+
+
+fn _metadata_field(path: &Path, field: &str, ptr: *const c_char) -> Result<String, KrustyErrorType> {
+    if ptr.is_null() {
+        import_error!(format!("{:?}: plugin metadata has no {}", path, field))
+    }
+    match unsafe { CStr::from_ptr(ptr) }.to_str() {
+        Ok(s) if !s.is_empty() => Ok(s.to_string()),
+        _ => import_error!(format!("{:?}: plugin metadata has an invalid {}", path, field))
+    }
+}
 
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Module {
     pub vars: ModuleVars,
     pub path: Option<PathBuf>,
+    // plain data such as parsed JSON: every member is public and `__type__`/`__exports__` are ordinary names
+    pub data: bool,
 }
 
 
@@ -44,30 +86,144 @@ impl Module {
         Module {
             vars: HashMap::new(),
             path,
+            data: false,
+        }
+    }
+
+    pub fn new_data() -> Module {
+        Module {
+            vars: HashMap::new(),
+            path: None,
+            data: true,
+        }
+    }
+
+    pub fn is_public(&self, name: &str) -> bool {
+        if self.data {
+            return true;
+        }
+        // an explicit `__exports__` list takes precedence over the leading underscore convention
+        match self.vars.get(MOD_EXPORTS_VAR) {
+            Some(Block::List(l)) => l.iter().any(|e| matches!(e, Block::Object(Token::Text(t)) if t == name)),
+            Some(Block::Object(Token::Text(t))) => t == name, // single element lists are unwrapped
+            _ => !name.starts_with('_'),
+        }
+    }
+
+    pub fn public_vars(&self) -> ModuleVars {
+        self.vars.iter()
+            .filter(|(k, _)| self.is_public(k))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    fn _check_dylib_metadata(path: &Path, lib: &libloading::Library) -> Result<(), KrustyErrorType> {
+        // validates the plugin before any of its code that depends on krusty-core runs
+        let meta = unsafe {
+            let metadata: libloading::Symbol<DynMetadataSignature> = match lib.get(METADATA_SYMBOL) {
+                Ok(f) => f,
+                Err(_) => import_error!(format!(
+                    "{:?}: missing symbol 'krusty_plugin_metadata'. Rust plugins must use the plugin_metadata! macro", path
+                ))
+            };
+            match metadata().as_ref() {
+                Some(m) => m,
+                None => import_error!(format!("{:?}: plugin metadata is null", path))
+            }
+        };
+        let name = _metadata_field(path, "name", meta.name)?;
+        let version = _metadata_field(path, "version", meta.version)?;
+        let core_version = _metadata_field(path, "core version", meta.core_version)?;
+        let expected = CORE_VERSION.trim_end_matches('\0');
+
+        print_verbose!("plugin {} {} (krusty-core {})", name, version, core_version);
+        if core_version != expected {
+            import_error!(format!(
+                "{:?}: plugin '{}' {} was built against krusty-core {}, but this interpreter uses {}",
+                path, name, version, core_version, expected
+            ))
         }
+        Ok(())
     }
 
-    fn _load_dylib_funcs(&mut self, lib: &libloading::Library) {
+    fn _load_dylib_funcs(&mut self, path: &Path, lib: &libloading::Library) -> Result<(), KrustyErrorType> {
+        // plugins built against the C ABI are preferred over the rust `load_all` entry point
+        if ffi::is_c_plugin(lib) {
+            return ffi::load_c_plugin(lib, path, &mut self.vars)
+        }
+        Module::_check_dylib_metadata(path, lib)?;
         unsafe {
-            let load_all: libloading::Symbol<DynLoadSignature> = lib.get(b"load_all").expect("library load error2");
+            let load_all: libloading::Symbol<DynLoadSignature> = match lib.get(LOAD_ALL_SYMBOL) {
+                Ok(f) => f,
+                Err(_) => import_error!(format!("{:?}: missing symbol 'load_all'", path))
+            };
             load_all(&mut self.vars);
         }
+        Ok(())
     }
 
-    pub fn load_dylib(&mut self) {
-        let path = self.path.clone().unwrap();
+    pub fn load_dylib(&mut self) -> Result<(), KrustyErrorType> {
+        let path = match &self.path {
+            Some(p) => p.clone(),
+            None => import_error!("native module has no library path")
+        };
         let loaded = match _DYLIB_REFS.lock().unwrap().get(&path) {
             Some(l) => {
-                self._load_dylib_funcs(l);
+                self._load_dylib_funcs(&path, l)?;
                 true
             }
             None => false,
         }; // release lock
 
         if !loaded {
-            let l = libloading::Library::new(&path).expect("library load error");
-            self._load_dylib_funcs(&l);
+            let l = match libloading::Library::new(&path) {
+                Ok(l) => l,
+                Err(e) => import_error!(format!("{:?}: unable to load library: {}", path, e))
+            };
+            self._load_dylib_funcs(&path, &l)?;
             _DYLIB_REFS.lock().unwrap().insert(path.clone(), l);
         }
+        Ok(())
+    }
+}
+
+
+
+// Modules imported by an interpreter, keyed by their canonical source path
+// `loading` tracks the chain of imports currently being executed so that cycles
+// can be reported instead of recursing forever
+#[derive(Debug, Default)]
+pub struct ModuleCache {
+    modules: HashMap<PathBuf, Module>,
+    loading: Vec<PathBuf>,
+}
+
+impl ModuleCache {
+    pub fn get(&self, path: &Path) -> Option<Module> {
+        self.modules.get(path).cloned()
+    }
+
+    pub fn insert(&mut self, path: &Path, module: Module) {
+        self.modules.insert(path.to_path_buf(), module);
+    }
+
+    pub fn begin_import(&mut self, path: &Path) -> Result<(), KrustyErrorType> {
+        if let Some(pos) = self.loading.iter().position(|p| p == path) {
+            let chain = self.loading[pos..].iter()
+                .map(|p| p.as_path())
+                .chain(std::iter::once(path))
+                .map(|p| p.to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+            import_error!(format!("import cycle: {}", chain))
+        }
+        self.loading.push(path.to_path_buf());
+        Ok(())
+    }
+
+    pub fn end_import(&mut self, path: &Path) {
+        if let Some(pos) = self.loading.iter().rposition(|p| p == path) {
+            self.loading.remove(pos);
+        }
     }
 }
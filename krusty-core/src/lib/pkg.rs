@@ -2,15 +2,24 @@ use std::env;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
 use dirs;
+use semver::{Version, VersionReq};
 
 use crate::syntax::evaluator::NameSpace;
 use crate::lib::errors::{Error, KrustyErrorType};
+use crate::lib::manifest::{Manifest, LockFile, LockedPackage, PackageIndex, InstalledPackage};
+use crate::lib::archive;
 
 pub const INSTALL_FOLDER: &'static str = ".krusty";
 pub const INSTALL_SUBFOLDER: &'static str = "pkg";
 pub const LANGUAGE_EXT: &'static str = "krt";
 pub const DIR_PKG_INITIALIZER: &'static str = "__pkg__.krt";
+pub const SEARCH_PATH_ENV: &'static str = "KRUSTY_PATH";
+pub const PROJECT_MODULES_DIR: &'static str = "krusty_modules";
+pub const REGISTRY_ENV: &'static str = "KRUSTY_REGISTRY";
+pub const REGISTRY_SUBFOLDER: &'static str = "registry";
+pub const CACHE_SUBFOLDER: &'static str = "cache";
 
 const DYLIB_INIT_COMMENT: &'static str = r#"# This file was created by Krusty's --install option
 # - allows for easy importing of native dylib package
@@ -18,14 +27,20 @@ const DYLIB_INIT_COMMENT: &'static str = r#"# This file was created by Krusty's
 "#;
 
 
-fn normalize_import_path(p: &mut PathBuf) {
+fn normalize_import_path(p: &mut PathBuf) -> Result<(), KrustyErrorType> {
     if p.is_dir() {
         // DIR_PKG_INITIALIZER file is imported if import() is used on the parent directory
         // Might be useful to write native modules
-        p.push(DIR_PKG_INITIALIZER);
+        // packages with a manifest may name a different entry point, a broken manifest is reported
+        if Manifest::exists_in(p) {
+            *p = Manifest::from_dir(p)?.entry_path(p)?;
+        } else {
+            p.push(DIR_PKG_INITIALIZER);
+        }
     } else if !p.ends_with(LANGUAGE_EXT) {
         p.set_extension(LANGUAGE_EXT);
     }
+    Ok(())
 }
 
 pub fn get_install_path() -> Result<PathBuf, KrustyErrorType> {
@@ -34,39 +49,70 @@ pub fn get_install_path() -> Result<PathBuf, KrustyErrorType> {
     Ok(install_path)
 }
 
+fn find_project_modules(start: &Path) -> Option<PathBuf> {
+    // looks for PROJECT_MODULES_DIR in the start directory and each of its ancestors
+    start.ancestors()
+        .map(|d| d.join(PROJECT_MODULES_DIR))
+        .find(|d| d.is_dir())
+}
+
+pub fn default_search_path(root: Option<&PathBuf>) -> Vec<PathBuf> {
+    // search path order after CWD and the calling module's directory
+    // 1. KRUSTY_PATH environment variable entries
+    // 2. project local PROJECT_MODULES_DIR, nearest to the root module or CWD
+    // 3. pkg installation directory
+    let mut paths: Vec<PathBuf> = Vec::new();
+    if let Some(v) = env::var_os(SEARCH_PATH_ENV) {
+        paths.extend(env::split_paths(&v).filter(|p| !p.as_os_str().is_empty()));
+    }
+
+    let mut start = match root {
+        Some(p) => p.clone(),
+        None => env::current_dir().unwrap_or(PathBuf::from(".")),
+    };
+    if start.is_file() {
+        start.pop();
+    }
+    if let Some(p) = find_project_modules(&start) {
+        paths.push(p);
+    }
+
+    if let Ok(mut pkg_path) = get_install_path() {
+        pkg_path.push(INSTALL_SUBFOLDER);
+        paths.push(pkg_path);
+    }
+    paths
+}
+
+// returns the canonical path of the module, which is also used as its import cache key
 pub fn search_for_module(ns: &NameSpace, name: &String) -> Result<PathBuf, KrustyErrorType> {
     // Checks for non native modules or files in different locations
     // 1. Check current working directory
+    // 2. Check relative path to calling module / namespace
+    // 3. Check each directory in the interpreter's search path
+    let mut candidates: Vec<PathBuf> = Vec::new();
+
     let mut cwd = env::current_dir().unwrap_or(PathBuf::from("."));
     cwd.push(name);
-    normalize_import_path(&mut cwd);
-
-    if cwd.is_file() {
-        print_verbose!("current dir");
-        return Ok(cwd)
-    }
+    candidates.push(cwd);
 
-    // 2. Check relative path to calling module / namespace
-    let mut relative = ns.get_relative_path(name);
-    normalize_import_path(&mut relative);
+    candidates.push(ns.get_relative_path(name));
 
-    if relative.is_file() {
-        print_verbose!("relative dir");
-        return Ok(relative)
+    for dir in ns.get_search_path().borrow().iter() {
+        candidates.push(dir.join(name));
     }
 
-    // 3. Check pkg installation directory
-    let mut pkg_path = get_install_path()?;
-    pkg_path.push(INSTALL_SUBFOLDER);
-    pkg_path.push(name);
-    normalize_import_path(&mut pkg_path);
-
-    if pkg_path.is_file() {
-        print_verbose!("pkg dir");
-        return Ok(pkg_path)
+    let mut tried: Vec<String> = Vec::new();
+    for mut p in candidates {
+        normalize_import_path(&mut p)?;
+        if p.is_file() {
+            print_verbose!("found {:?}", p);
+            return Ok(fs::canonicalize(p)?)
+        }
+        tried.push(format!("  {}", p.display()));
     }
 
-    import_error!(format!("'{}' Not found", name))
+    import_error!(format!("'{}' Not found. Tried:\n{}", name, tried.join("\n")))
 }
 
 
@@ -161,30 +207,281 @@ pub fn copy_dir<U: AsRef<Path>, V: AsRef<Path>>(from: U, to: V) -> Result<(), Kr
 }
 
 
-pub fn install_pkg(path_str: &String) -> Result<(), KrustyErrorType> {
-    // installs a package directory, language file or native dylib
-    // 1. test for package directory (must contain DIR_PKG_INITIALIZER file)
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    pub registry: Option<PathBuf>,
+    pub force: bool, // overwrite packages that are already installed
+}
+
+
+pub fn get_registry_path(opts: &InstallOptions) -> Option<PathBuf> {
+    // local directory registry laid out as <registry>/<name>/<version>/krusty.toml
+    if let Some(r) = &opts.registry {
+        return Some(r.clone())
+    }
+    if let Some(r) = env::var_os(REGISTRY_ENV) {
+        return Some(PathBuf::from(r))
+    }
+    let mut r = get_install_path().ok()?;
+    r.push(REGISTRY_SUBFOLDER);
+    if r.is_dir() { Some(r) } else { None }
+}
+
+
+struct ResolvedPackage {
+    manifest: Manifest,
+    dir: PathBuf,
+    source: String,
+}
+
+
+fn find_in_registry(
+    name: &str,
+    req: &VersionReq,
+    locked: Option<&LockFile>,
+    registry: Option<&PathBuf>) -> Result<PathBuf, KrustyErrorType> {
+
+    let pkg_dir = match registry {
+        Some(r) => r.join(name),
+        None => generic_error!(format!("dependency '{}' has no path and no registry is configured", name))
+    };
+    if !pkg_dir.is_dir() {
+        generic_error!(format!("dependency '{}' not found in registry {:?}", name, pkg_dir))
+    }
+
+    let mut versions: Vec<Version> = Vec::new();
+    for entry in fs::read_dir(&pkg_dir)? {
+        let entry = entry?;
+        if let Some(v) = entry.file_name().to_str().and_then(|v| Version::parse(v).ok()) {
+            if req.matches(&v) {
+                versions.push(v);
+            }
+        }
+    }
+
+    // a compatible version recorded in the lockfile wins over the newest one
+    let locked_version = locked.and_then(|l| l.locked_version(name)).filter(|v| versions.contains(v));
+    match locked_version.or_else(|| versions.into_iter().max()) {
+        Some(v) => Ok(fs::canonicalize(pkg_dir.join(v.to_string()))?),
+        None => generic_error!(format!("no version of '{}' in registry matches '{}'", name, req))
+    }
+}
+
+
+fn resolve_dependencies(
+    manifest: &Manifest,
+    dir: &Path,
+    locked: Option<&LockFile>,
+    registry: Option<&PathBuf>,
+    resolved: &mut BTreeMap<String, ResolvedPackage>) -> Result<(), KrustyErrorType> {
+
+    for (name, dep) in &manifest.dependencies {
+        let req = dep.version_req()?;
+        if let Some(r) = resolved.get(name) {
+            // packages are installed flat, so only one version of each can be used
+            let v = r.manifest.version()?;
+            if !req.matches(&v) {
+                generic_error!(format!(
+                    "'{}' requires {} '{}', but version {} was already resolved",
+                    manifest.package.name, name, req, v
+                ))
+            }
+            continue;
+        }
+
+        let (dep_dir, source) = match dep.path() {
+            Some(p) => {
+                let d = match fs::canonicalize(dir.join(p)) {
+                    Ok(d) => d,
+                    Err(_) => generic_error!(format!("dependency '{}' path {:?} not found", name, p))
+                };
+                let source = format!("path+{}", d.display());
+                (d, source)
+            },
+            None => {
+                let d = find_in_registry(name, &req, locked, registry)?;
+                let source = format!("registry+{}", d.display());
+                (d, source)
+            }
+        };
+
+        let dep_manifest = Manifest::from_dir(&dep_dir)?;
+        dep_manifest.validate(&dep_dir)?;
+        if &dep_manifest.package.name != name {
+            generic_error!(format!("dependency '{}' points to package '{}'", name, dep_manifest.package.name))
+        }
+        let v = dep_manifest.version()?;
+        if !req.matches(&v) {
+            generic_error!(format!("dependency '{}' version {} does not match '{}'", name, v, req))
+        }
+
+        let sub = dep_manifest.clone();
+        resolved.insert(name.clone(), ResolvedPackage{manifest: dep_manifest, dir: dep_dir.clone(), source});
+        resolve_dependencies(&sub, &dep_dir, locked, registry, resolved)?;
+    }
+    Ok(())
+}
+
+
+fn locked_package(manifest: &Manifest, source: &str, resolved: &BTreeMap<String, ResolvedPackage>) -> LockedPackage {
+    let dependencies = manifest.dependencies.keys()
+        .filter_map(|d| resolved.get(d))
+        .map(|d| format!("{} {}", d.manifest.package.name, d.manifest.package.version))
+        .collect();
+    LockedPackage {
+        name: manifest.package.name.clone(),
+        version: manifest.package.version.clone(),
+        source: source.to_string(),
+        dependencies,
+    }
+}
+
+
+fn prepare_install_dst(name: &str, dst_path: &Path, index: &PackageIndex, opts: &InstallOptions) -> Result<(), KrustyErrorType> {
+    // refuses to overwrite an installed package unless forced
+    if fs::symlink_metadata(dst_path).is_err() {
+        return Ok(())
+    }
+    if !opts.force {
+        let version = index.get(name).and_then(|p| p.version.clone()).unwrap_or("unversioned".to_string());
+        generic_error!(format!("'{}' is already installed ({}). Use --force to overwrite", name, version))
+    }
+    println!(" remove: {:?}", dst_path);
+    if dst_path.is_dir() {
+        fs::remove_dir_all(dst_path)?;
+    } else {
+        fs::remove_file(dst_path)?;
+    }
+    Ok(())
+}
+
+
+fn install_manifest_pkg(
+    pkg_path: &Path,
+    source: Option<String>,
+    index: &mut PackageIndex,
+    opts: &InstallOptions) -> Result<(), KrustyErrorType> {
+
+    // validates the manifest, resolves dependencies recursively, copies every resolved package
+    // into the pkg installation directory and writes the lockfile next to the installed package.
+    // packages with a known source (registry, archive) are installed as is, without writing a lockfile
+    let pkg_path = fs::canonicalize(pkg_path)?;
+    let manifest = Manifest::from_dir(&pkg_path)?;
+    manifest.validate(&pkg_path)?;
+    println!("Package {} {}", manifest.package.name, manifest.package.version);
+
+    let locked = LockFile::from_dir(&pkg_path)?;
+    let registry = get_registry_path(opts);
+    let mut resolved: BTreeMap<String, ResolvedPackage> = BTreeMap::new();
+    resolve_dependencies(&manifest, &pkg_path, locked.as_ref(), registry.as_ref(), &mut resolved)?;
+    if resolved.contains_key(&manifest.package.name) {
+        generic_error!(format!("package '{}' depends on itself", manifest.package.name))
+    }
+
+    let mut dst_path = get_install_path()?;
+    dst_path.push(INSTALL_SUBFOLDER);
+    let pkg_dst = dst_path.join(&manifest.package.name);
+    prepare_install_dst(&manifest.package.name, &pkg_dst, index, opts)?;
+
+    let lock = match source {
+        Some(_) => None,
+        None => {
+            let mut lock = LockFile::default();
+            lock.packages.push(locked_package(&manifest, &format!("path+{}", pkg_path.display()), &resolved));
+            for r in resolved.values() {
+                lock.packages.push(locked_package(&r.manifest, &r.source, &resolved));
+            }
+            Some(lock)
+        }
+    };
+    let source = source.unwrap_or(format!("path+{}", pkg_path.display()));
+
+    for r in resolved.values() {
+        let name = &r.manifest.package.name;
+        let version = &r.manifest.package.version;
+        let installed = index.get(name).and_then(|p| p.version.as_ref());
+        if installed == Some(version) {
+            println!("Dependency {} {} is up to date", name, version);
+            continue;
+        }
+        println!("Installing dependency {} {}", name, version);
+        let dep_dst = dst_path.join(name);
+        prepare_install_dst(name, &dep_dst, index, opts)?;
+        copy_dir(&r.dir, &dep_dst)?;
+        index.insert(installed_package(&r.manifest, &r.source, &dep_dst));
+    }
+    copy_dir(&pkg_path, &pkg_dst)?;
+    if let Some(lock) = lock {
+        // the source directory is left untouched
+        let lock_path = lock.write(&pkg_dst)?;
+        println!("  lock: {:?}", lock_path);
+    }
+    index.insert(installed_package(&manifest, &source, &pkg_dst));
+    Ok(())
+}
+
+
+fn installed_package(manifest: &Manifest, source: &str, location: &Path) -> InstalledPackage {
+    InstalledPackage {
+        name: manifest.package.name.clone(),
+        version: Some(manifest.package.version.clone()),
+        source: source.to_string(),
+        location: location.to_string_lossy().to_string(),
+        dependencies: manifest.dependencies.keys().cloned().collect(),
+    }
+}
+
+
+fn unversioned_package(name: &str, pkg_path: &Path, location: &Path) -> Result<InstalledPackage, KrustyErrorType> {
+    Ok(InstalledPackage {
+        name: name.to_string(),
+        version: None,
+        source: format!("path+{}", fs::canonicalize(pkg_path)?.display()),
+        location: location.to_string_lossy().to_string(),
+        dependencies: vec![],
+    })
+}
+
+
+pub fn install_pkg(path_str: &String, opts: &InstallOptions) -> Result<(), KrustyErrorType> {
+    // installs a package directory, package archive, language file or native dylib
+    // 1. test for package directory with a manifest (see manifest.rs)
+    // 2. test for package archive created by `krusty pkg pack` (see archive.rs)
+    // 3. test for package directory (must contain DIR_PKG_INITIALIZER file)
     println!("Installing {:?}", path_str);
     let pkg_path = PathBuf::from(path_str);
 
-    let mut dst_path = get_install_path()?;
+    let install_path = get_install_path()?;
+    let mut index = PackageIndex::from_dir(&install_path)?;
+    let mut dst_path = install_path.clone();
     dst_path.push(INSTALL_SUBFOLDER);
 
-    if pkg_path.is_dir() {
+    if pkg_path.is_dir() && Manifest::exists_in(&pkg_path) {
+        install_manifest_pkg(&pkg_path, None, &mut index, opts)?;
+
+    } else if archive::is_pkg_archive(&pkg_path) {
+        let unpacked = archive::unpack_pkg(&pkg_path)?;
+        let source = format!("archive+{}", fs::canonicalize(&pkg_path)?.display());
+        install_manifest_pkg(&unpacked, Some(source), &mut index, opts)?;
+
+    } else if pkg_path.is_dir() {
         let mut init_file = PathBuf::from(path_str);
         init_file.push(DIR_PKG_INITIALIZER);
         if !init_file.is_file() {
             generic_error!(format!("Package does not contain '{}'", DIR_PKG_INITIALIZER))
         }
 
-        let dirname = pkg_path.file_name()
+        let dirname = fs::canonicalize(&pkg_path)?
+            .file_name()
             .ok_or("filename not valid")?
             .to_str()
             .ok_or("filename not valid")?
             .to_owned();
 
-        dst_path.push(dirname);
+        dst_path.push(&dirname);
+        prepare_install_dst(&dirname, &dst_path, &index, opts)?;
         copy_dir(&pkg_path, &dst_path)?;
+        index.insert(unversioned_package(&dirname, &pkg_path, &dst_path)?);
 
     } else {
         let ext = pkg_path.extension()
@@ -199,9 +496,15 @@ pub fn install_pkg(path_str: &String) -> Result<(), KrustyErrorType> {
                 .to_str()
                 .ok_or("filename not valid")?
                 .to_owned();
+            let name = pkg_path.file_stem()
+                .ok_or("filename not valid")?
+                .to_string_lossy()
+                .to_string();
             dst_path.push(&filename);
+            prepare_install_dst(&name, &dst_path, &index, opts)?;
             println!("copy: {:?}", filename);
             fs::copy(&pkg_path, &dst_path)?;
+            index.insert(unversioned_package(&name, &pkg_path, &dst_path)?);
 
         } else {
             let mut fstem = pkg_path.file_stem()
@@ -215,6 +518,8 @@ pub fn install_pkg(path_str: &String) -> Result<(), KrustyErrorType> {
             }
 
             dst_path.push(fstem.clone());
+            prepare_install_dst(&fstem, &dst_path, &index, opts)?;
+            let pkg_dst = dst_path.clone();
             if fs::metadata(&dst_path).is_err() {
                 println!(" mkdir: {:?}", dst_path);
                 fs::create_dir_all(&dst_path)?;
@@ -240,9 +545,142 @@ pub fn install_pkg(path_str: &String) -> Result<(), KrustyErrorType> {
             dst_path.set_file_name(&fname);
             fs::copy(&pkg_path, &dst_path)?;
             println!("  copy: {:?}", fname);
+            index.insert(unversioned_package(&fstem, &pkg_path, &pkg_dst)?);
+
+        }
+    }
+
+    index.write(&install_path)?;
+    Ok(())
+}
+
+
+pub fn list_pkgs() -> Result<(), KrustyErrorType> {
+    let index = PackageIndex::from_dir(&get_install_path()?)?;
+    if index.packages.is_empty() {
+        println!("No packages installed");
+    }
+    for p in &index.packages {
+        println!("{} {}", p.name, p.version.as_deref().unwrap_or("(unversioned)"));
+    }
+    Ok(())
+}
 
+
+pub fn pkg_info(name: &str) -> Result<(), KrustyErrorType> {
+    let index = PackageIndex::from_dir(&get_install_path()?)?;
+    let p = match index.get(name) {
+        Some(p) => p,
+        None => generic_error!(format!("'{}' is not installed", name))
+    };
+    println!("name: {}", p.name);
+    println!("version: {}", p.version.as_deref().unwrap_or("(unversioned)"));
+    if let Ok(m) = Manifest::from_dir(Path::new(&p.location)) {
+        if !m.package.description.is_empty() {
+            println!("description: {}", m.package.description);
         }
+        println!("entry: {}", m.package.entry);
+    }
+    println!("source: {}", p.source);
+    println!("location: {}", p.location);
+    if !p.dependencies.is_empty() {
+        println!("dependencies: {}", p.dependencies.join(", "));
+    }
+    let dependents: Vec<&str> = index.dependents(name).iter().map(|d| d.name.as_str()).collect();
+    if !dependents.is_empty() {
+        println!("required by: {}", dependents.join(", "));
+    }
+    Ok(())
+}
+
+
+pub fn uninstall_pkg(name: &str, opts: &InstallOptions) -> Result<(), KrustyErrorType> {
+    let install_path = get_install_path()?;
+    let mut index = PackageIndex::from_dir(&install_path)?;
+    let p = match index.get(name) {
+        Some(p) => p.clone(),
+        None => generic_error!(format!("'{}' is not installed", name))
+    };
+
+    let dependents: Vec<String> = index.dependents(name).iter().map(|d| d.name.clone()).collect();
+    if !dependents.is_empty() && !opts.force {
+        generic_error!(format!("'{}' is required by {}. Use --force to uninstall anyway", name, dependents.join(", ")))
+    }
+
+    let location = PathBuf::from(&p.location);
+    println!("Uninstalling {} {}", p.name, p.version.as_deref().unwrap_or("(unversioned)"));
+    if location.is_dir() {
+        fs::remove_dir_all(&location)?;
+    } else if location.is_file() {
+        fs::remove_file(&location)?;
+    }
+    println!(" remove: {:?}", location);
+    index.remove(name);
+    index.write(&install_path)?;
+    Ok(())
+}
+
+
+fn latest_source_version(p: &InstalledPackage, index: &PackageIndex) -> Option<(Version, PathBuf)> {
+    // path packages are upgraded from their source directory
+    // registry packages are upgraded to the newest version in the registry
+    // that still satisfies every installed package depending on it
+    if let Some(dir) = p.source.strip_prefix("path+") {
+        let dir = PathBuf::from(dir);
+        let v = Manifest::from_dir(&dir).ok()?.version().ok()?;
+        return Some((v, dir))
     }
+    let reqs: Vec<VersionReq> = index.dependents(&p.name).iter()
+        .filter_map(|d| Manifest::from_dir(Path::new(&d.location)).ok())
+        .filter_map(|m| m.dependencies.get(&p.name)?.version_req().ok())
+        .collect();
+
+    // registry sources point to <registry>/<name>/<version>
+    let pkg_dir = PathBuf::from(p.source.strip_prefix("registry+")?).parent()?.to_path_buf();
+    fs::read_dir(&pkg_dir).ok()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| Version::parse(e.file_name().to_str()?).ok())
+        .filter(|v| reqs.iter().all(|r| r.matches(v)))
+        .max()
+        .map(|v| (v.clone(), pkg_dir.join(v.to_string())))
+}
+
 
+pub fn upgrade_pkgs(name: Option<&String>, opts: &InstallOptions) -> Result<(), KrustyErrorType> {
+    // reinstalls versioned packages whose source has a newer version available
+    let install_path = get_install_path()?;
+    let mut index = PackageIndex::from_dir(&install_path)?;
+    if let Some(n) = name {
+        if index.get(n).is_none() {
+            generic_error!(format!("'{}' is not installed", n))
+        }
+    }
+    let force = InstallOptions{force: true, ..opts.clone()};
+    let mut upgraded = 0;
+    for p in index.packages.clone() {
+        if name.is_some() && name != Some(&p.name) {
+            continue;
+        }
+        let current = match p.version.as_ref().and_then(|v| Version::parse(v).ok()) {
+            Some(v) => v,
+            None => continue, // unversioned packages can't be compared
+        };
+        match latest_source_version(&p, &index) {
+            Some((latest, dir)) if latest > current => {
+                println!("Upgrading {} {} -> {}", p.name, current, latest);
+                let source = if p.source.starts_with("registry+") {
+                    Some(format!("registry+{}", dir.display()))
+                } else {
+                    None
+                };
+                install_manifest_pkg(&dir, source, &mut index, &force)?;
+                upgraded += 1;
+            },
+            Some(_) => println!("{} {} is up to date", p.name, current),
+            None => println!("{} {}: source not available", p.name, current),
+        }
+    }
+    println!("{} package(s) upgraded", upgraded);
+    index.write(&install_path)?;
     Ok(())
 }
@@ -0,0 +1,181 @@
+/* this file contains the `random` builtin module
+*
+* numbers come from xoshiro256**, so a seeded generator yields the same sequence on every platform and version.
+* The module functions share a default generator, `random.generator(seed)` creates independent ones
+*
+* rng = random.generator(42);
+* roll = rng.randint(1, 6);
+*
+*/
+
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::rc::Rc;
+
+use crate::syntax::lexer::Token;
+use crate::syntax::parser::Block;
+use crate::syntax::evaluator::NameSpace;
+
+use super::errors::{Error, KrustyErrorType};
+use super::moddef::{Module, ModuleVars};
+use super::convert::{arg, list_arg};
+use super::helper;
+
+
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn from_seed(seed: u64) -> Rng {
+        // splitmix64 spreads the seed over the whole state, which must not be all zeros
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        Rng { state: [next(), next(), next(), next()] }
+    }
+
+    pub fn from_entropy() -> Rng {
+        // std seeds RandomState randomly for every process
+        Rng::from_seed(RandomState::new().build_hasher().finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        // 53 random bits, uniform in [0, 1)
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        // uniform in [0, n) without modulo bias
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let v = self.next_u64();
+            if v < zone {
+                return v % n;
+            }
+        }
+    }
+}
+
+
+fn _seed(rng: &RefCell<Rng>, args: &[Block]) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    let seed: i64 = arg("seed", args, 0)?;
+    *rng.borrow_mut() = Rng::from_seed(seed as u64);
+    Ok(Block::Null)
+}
+
+fn _random(rng: &RefCell<Rng>, args: &[Block]) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 0);
+    Ok(Block::Object(Token::Number(rng.borrow_mut().next_f64())))
+}
+
+fn _randint(rng: &RefCell<Rng>, args: &[Block]) -> Result<Block, KrustyErrorType> {
+    // both ends are included
+    func_nargs_eq!(args, 2);
+    let low: i64 = arg("randint", args, 0)?;
+    let high: i64 = arg("randint", args, 1)?;
+    if low > high {
+        eval_error!(format!("randint() empty range {}..{}", low, high))
+    }
+    let span = (high as i128 - low as i128 + 1) as u128;
+    let offset = if span > u64::MAX as u128 {
+        rng.borrow_mut().next_u64()
+    } else {
+        rng.borrow_mut().below(span as u64)
+    };
+    Ok(Block::Object(Token::Number((low as i128 + offset as i128) as f64)))
+}
+
+fn _choice(rng: &RefCell<Rng>, args: &[Block]) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    let l = list_arg("choice", args, 0)?;
+    if l.is_empty() {
+        eval_error!("choice() from empty list")
+    }
+    let idx = rng.borrow_mut().below(l.len() as u64) as usize;
+    Ok(l[idx].clone())
+}
+
+fn _shuffle(rng: &RefCell<Rng>, args: &[Block]) -> Result<Block, KrustyErrorType> {
+    // returns a shuffled copy, like the list builtins
+    func_nargs_eq!(args, 1);
+    let mut l = list_arg("shuffle", args, 0)?;
+    let mut rng = rng.borrow_mut();
+    for i in (1..l.len()).rev() {
+        let j = rng.below(i as u64 + 1) as usize;
+        l.swap(i, j);
+    }
+    Ok(Block::List(l))
+}
+
+fn _sample(rng: &RefCell<Rng>, args: &[Block]) -> Result<Block, KrustyErrorType> {
+    // k items at distinct positions, in random order
+    func_nargs_eq!(args, 2);
+    let mut l = list_arg("sample", args, 0)?;
+    let k: usize = arg("sample", args, 1)?;
+    if k > l.len() {
+        eval_error!(format!("sample() of {} items from a list of length {}", k, l.len()))
+    }
+    let mut rng = rng.borrow_mut();
+    for i in 0..k {
+        let j = i + rng.below((l.len() - i) as u64) as usize;
+        l.swap(i, j);
+    }
+    l.truncate(k);
+    Ok(Block::List(l))
+}
+
+
+fn _load_generator(vars: &mut ModuleVars, rng: Rc<RefCell<Rng>>) {
+    let r = rng.clone();
+    helper::load_closure(vars, "seed", move |_ns, args| _seed(&r, args));
+    let r = rng.clone();
+    helper::load_closure(vars, "random", move |_ns, args| _random(&r, args));
+    let r = rng.clone();
+    helper::load_closure(vars, "randint", move |_ns, args| _randint(&r, args));
+    let r = rng.clone();
+    helper::load_closure(vars, "choice", move |_ns, args| _choice(&r, args));
+    let r = rng.clone();
+    helper::load_closure(vars, "shuffle", move |_ns, args| _shuffle(&r, args));
+    helper::load_closure(vars, "sample", move |_ns, args| _sample(&rng, args));
+}
+
+fn _generator(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // generator() is seeded randomly, generator(seed) reproducibly
+    func_nargs_le!(args, 1);
+    let rng = match args.len() {
+        0 => Rng::from_entropy(),
+        _ => Rng::from_seed(arg::<i64>("generator", args, 0)? as u64),
+    };
+    let mut m = Module::new(None);
+    _load_generator(&mut m.vars, Rc::new(RefCell::new(rng)));
+    Ok(Block::Mod(m))
+}
+
+
+pub fn load_random(env_native: &mut ModuleVars) {
+    let mut m = Module::new(None);
+    _load_generator(&mut m.vars, Rc::new(RefCell::new(Rng::from_entropy())));
+    helper::load_func(&mut m.vars, "generator", _generator);
+    env_native.insert("random".to_string(), Block::Mod(m));
+}
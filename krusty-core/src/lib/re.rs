@@ -0,0 +1,135 @@
+/* this file contains the `re` builtin module, backed by the regex crate
+*
+* `re.compile(pattern)` returns a regex object, `match` and `search` return a match object or null.
+* Match positions count characters like the string module
+*
+* date = re.compile('(?P<year>\d{4})-(?P<month>\d{2})');
+* m = date.search('released 2021-07');
+* year = m.group('year');
+*
+*/
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use regex::{Captures, Regex};
+
+use crate::syntax::lexer::Token;
+use crate::syntax::parser::Block;
+use crate::syntax::evaluator::NameSpace;
+
+use super::errors::{Error, KrustyErrorType};
+use super::moddef::{Module, ModuleVars};
+use super::convert::{arg, new_number, new_text, type_name};
+use super::string::char_index;
+use super::helper;
+
+
+fn _match_object(re: &Regex, text: &str, caps: &Captures) -> Block {
+    let whole = caps.get(0).expect("group 0 is always set");
+    let groups: Vec<Option<String>> = caps.iter().map(|g| g.map(|g| g.as_str().to_string())).collect();
+    let names: HashMap<String, usize> = re.capture_names()
+        .enumerate()
+        .filter_map(|(i, n)| n.map(|n| (n.to_string(), i)))
+        .collect();
+
+    let mut m = Module::new(None);
+    m.vars.insert("text".to_string(), new_text(whole.as_str()));
+    m.vars.insert("start".to_string(), new_number(char_index(text, whole.start()) as f64));
+    m.vars.insert("end".to_string(), new_number(char_index(text, whole.end()) as f64));
+    // groups 1.., null for groups that did not take part in the match
+    m.vars.insert("groups".to_string(), Block::List(
+        groups.iter().skip(1).map(|g| g.as_deref().map_or(Block::Null, new_text)).collect()
+    ));
+    helper::load_closure(&mut m.vars, "group", move |_ns, args| {
+        // group(), group(index) or group(name)
+        func_nargs_le!(args, 1);
+        let idx = match args.first() {
+            None => 0,
+            Some(Block::Object(Token::Text(name))) => match names.get(name) {
+                Some(i) => *i,
+                None => eval_error!(format!("group() no group named '{}'", name))
+            },
+            Some(_) => arg::<usize>("group", args, 0)?,
+        };
+        match groups.get(idx) {
+            Some(g) => Ok(g.as_deref().map_or(Block::Null, new_text)),
+            None => eval_error!(format!("group() no group {}, the pattern has {}", idx, groups.len() - 1))
+        }
+    });
+    Block::Mod(m)
+}
+
+fn _regex_object(re: Regex) -> Block {
+    let re = Rc::new(re);
+    let mut m = Module::new(None);
+    m.vars.insert("pattern".to_string(), new_text(re.as_str()));
+
+    let r = re.clone();
+    helper::load_closure(&mut m.vars, "match", move |_ns, args| {
+        // only matches at the start of the text
+        func_nargs_eq!(args, 1);
+        let text: String = arg("match", args, 0)?;
+        Ok(match r.captures(&text) {
+            Some(caps) if caps.get(0).map(|g| g.start()) == Some(0) => _match_object(&r, &text, &caps),
+            _ => Block::Null,
+        })
+    });
+    let r = re.clone();
+    helper::load_closure(&mut m.vars, "search", move |_ns, args| {
+        func_nargs_eq!(args, 1);
+        let text: String = arg("search", args, 0)?;
+        Ok(match r.captures(&text) {
+            Some(caps) => _match_object(&r, &text, &caps),
+            None => Block::Null,
+        })
+    });
+    let r = re.clone();
+    helper::load_closure(&mut m.vars, "find_all", move |_ns, args| {
+        // the text of every non-overlapping match
+        func_nargs_eq!(args, 1);
+        let text: String = arg("find_all", args, 0)?;
+        Ok(Block::List(r.find_iter(&text).map(|f| new_text(f.as_str())).collect()))
+    });
+    let r = re.clone();
+    helper::load_closure(&mut m.vars, "replace", move |_ns, args| {
+        // replace(text, replacement, count?), `$1` and `${name}` insert groups, a count of 0 replaces all
+        func_nargs_ge!(args, 2);
+        func_nargs_le!(args, 3);
+        let text: String = arg("replace", args, 0)?;
+        let rep: String = arg("replace", args, 1)?;
+        let count: usize = if args.len() == 3 { arg("replace", args, 2)? } else { 0 };
+        Ok(Block::Object(Token::Text(r.replacen(&text, count, rep.as_str()).into_owned())))
+    });
+    helper::load_closure(&mut m.vars, "split", move |_ns, args| {
+        func_nargs_eq!(args, 1);
+        let text: String = arg("split", args, 0)?;
+        Ok(Block::List(re.split(&text).map(new_text).collect()))
+    });
+    Block::Mod(m)
+}
+
+
+fn _compile(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    let pattern = match &args[0] {
+        Block::Object(Token::Text(p)) => p,
+        b => eval_error!(format!("compile() argument 1: expected Text, found {}", type_name(b)))
+    };
+    match Regex::new(pattern) {
+        Ok(re) => Ok(_regex_object(re)),
+        Err(e) => eval_error!(format!("compile() invalid pattern: {}", e))
+    }
+}
+
+fn _escape(t: String) -> Result<String, KrustyErrorType> {
+    Ok(regex::escape(&t))
+}
+
+
+pub fn load_re(env_native: &mut ModuleVars) {
+    let mut m = Module::new(None);
+    helper::load_func(&mut m.vars, "compile", _compile);
+    helper::load_typed(&mut m.vars, "escape", _escape);
+    env_native.insert("re".to_string(), Block::Mod(m));
+}
@@ -0,0 +1,67 @@
+/* this file describes the restrictions applied to untrusted scripts
+*
+* A disabled sandbox (the default) allows everything. Once enabled
+* - `import` only loads modules located under one of `import_roots`
+* - `import_native` only loads the libraries listed in `native_modules`, and nothing when the list is empty
+*
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::lib::errors::{Error, KrustyErrorType};
+use crate::lib::pkg;
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    pub enabled: bool,
+    pub import_roots: Vec<PathBuf>,
+    pub native_modules: Vec<PathBuf>, // library paths, with or without the platform prefix and extension
+}
+
+
+fn _canonical(p: &Path) -> Option<PathBuf> {
+    fs::canonicalize(p).ok()
+}
+
+
+impl Sandbox {
+    pub fn restricted() -> Sandbox {
+        Sandbox {
+            enabled: true,
+            ..Sandbox::default()
+        }
+    }
+
+    pub fn check_import(&self, path: &Path) -> Result<(), KrustyErrorType> {
+        if !self.enabled {
+            return Ok(())
+        }
+        let allowed = match _canonical(path) {
+            Some(p) => self.import_roots.iter()
+                .filter_map(|r| _canonical(r))
+                .any(|r| p.starts_with(r)),
+            None => false,
+        };
+        if !allowed {
+            import_error!(format!("sandbox: importing {:?} is not allowed", path))
+        }
+        Ok(())
+    }
+
+    pub fn check_native(&self, path: &Path) -> Result<(), KrustyErrorType> {
+        if !self.enabled {
+            return Ok(())
+        }
+        let lib = _canonical(path);
+        let allowed = lib.is_some() && self.native_modules.iter().any(|m| {
+            let mut m = m.clone();
+            pkg::to_native_dylib_name(&mut m).is_ok() && _canonical(&m) == lib
+        });
+        if !allowed {
+            import_error!(format!("sandbox: native module {:?} is not allowed", path))
+        }
+        Ok(())
+    }
+}
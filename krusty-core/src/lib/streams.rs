@@ -0,0 +1,67 @@
+/* this file holds the input and output streams used by an interpreter
+*
+* `print`, `eprint` and `input` go through these instead of the process stdio,
+* so an embedding host can redirect them. `CaptureBuffer` collects output in memory
+*
+*/
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+
+pub struct Streams {
+    pub stdout: Box<dyn Write>,
+    pub stderr: Box<dyn Write>,
+    pub stdin: Box<dyn BufRead>,
+}
+
+impl Default for Streams {
+    fn default() -> Self {
+        Streams {
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            stdin: Box::new(io::BufReader::new(io::stdin())),
+        }
+    }
+}
+
+impl fmt::Debug for Streams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Streams").finish()
+    }
+}
+
+
+// cloneable in-memory writer. Clones share the same buffer,
+// so one can be handed to the interpreter and the other read by the host
+#[derive(Debug, Clone, Default)]
+pub struct CaptureBuffer {
+    buf: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CaptureBuffer {
+    pub fn new() -> CaptureBuffer {
+        CaptureBuffer::default()
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.buf.borrow()).to_string()
+    }
+
+    pub fn clear(&self) {
+        self.buf.borrow_mut().clear();
+    }
+}
+
+impl Write for CaptureBuffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+/* this file contains the `string` builtin module
+*
+* positions and lengths count characters, not bytes, the same way `len` and indexing do
+*
+* words = string.split('a b c');
+* msg = string.format('{} has {} words', 'text', len(words));
+*
+*/
+
+use crate::syntax::lexer::Token;
+use crate::syntax::parser::Block;
+use crate::syntax::evaluator::NameSpace;
+
+use super::errors::{Error, KrustyErrorType};
+use super::moddef::{Module, ModuleVars};
+use super::convert::{arg, new_text, type_name};
+use super::helper;
+
+
+// texts built by repeat() may not grow beyond this many bytes
+pub const MAX_TEXT_LEN: usize = 1 << 28;
+
+pub fn to_text(b: &Block) -> String {
+    // text without quotes, numbers without a trailing .0
+    match b {
+        Block::Object(Token::Text(t)) => t.clone(),
+        Block::Object(Token::Number(n)) => n.to_string(),
+        _ => b.to_string(),
+    }
+}
+
+pub fn char_index(t: &str, byte_idx: usize) -> usize {
+    t[..byte_idx].chars().count()
+}
+
+fn _byte_index(func: &str, t: &str, char_idx: usize) -> Result<usize, KrustyErrorType> {
+    // the end of the text is a valid position
+    match t.char_indices().map(|(b, _)| b).chain(std::iter::once(t.len())).nth(char_idx) {
+        Some(b) => Ok(b),
+        None => eval_error!(format!("{}() index {} out of range for text of length {}", func, char_idx, t.chars().count()))
+    }
+}
+
+
+// ================ typed functions ================
+
+fn _trim(t: String) -> Result<String, KrustyErrorType> {
+    Ok(t.trim().to_string())
+}
+
+fn _upper(t: String) -> Result<String, KrustyErrorType> {
+    Ok(t.to_uppercase())
+}
+
+fn _lower(t: String) -> Result<String, KrustyErrorType> {
+    Ok(t.to_lowercase())
+}
+
+fn _replace(t: String, from: String, to: String) -> Result<String, KrustyErrorType> {
+    if from.is_empty() {
+        eval_error!("replace() pattern must not be empty")
+    }
+    Ok(t.replace(&from, &to))
+}
+
+fn _starts_with(t: String, prefix: String) -> Result<bool, KrustyErrorType> {
+    Ok(t.starts_with(&prefix))
+}
+
+fn _ends_with(t: String, suffix: String) -> Result<bool, KrustyErrorType> {
+    Ok(t.ends_with(&suffix))
+}
+
+fn _find(t: String, sub: String) -> Result<i64, KrustyErrorType> {
+    // character position of the first match, -1 when there is none
+    Ok(match t.find(&sub) {
+        Some(b) => char_index(&t, b) as i64,
+        None => -1,
+    })
+}
+
+fn _repeat(t: String, n: usize) -> Result<String, KrustyErrorType> {
+    match t.len().checked_mul(n) {
+        Some(size) if size <= MAX_TEXT_LEN => Ok(t.repeat(n)),
+        _ => eval_error!(format!("repeat() result would be longer than {} bytes", MAX_TEXT_LEN))
+    }
+}
+
+fn _chars(t: String) -> Result<Vec<String>, KrustyErrorType> {
+    Ok(t.chars().map(|c| c.to_string()).collect())
+}
+
+fn _to_number(t: String) -> Result<f64, KrustyErrorType> {
+    match t.trim().parse::<f64>() {
+        Ok(n) => Ok(n),
+        Err(_) => eval_error!(format!("to_number() cannot convert '{}' to Number", t))
+    }
+}
+
+fn _to_string(b: Block) -> Result<String, KrustyErrorType> {
+    Ok(to_text(&b))
+}
+
+
+// ================ optional and variadic arguments ================
+
+fn _split(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // splits on whitespace when no separator is given
+    func_nargs_ge!(args, 1);
+    func_nargs_le!(args, 2);
+    let t: String = arg("split", args, 0)?;
+    let parts: Vec<Block> = if args.len() == 2 {
+        let sep: String = arg("split", args, 1)?;
+        if sep.is_empty() {
+            eval_error!("split() separator must not be empty, use chars() instead")
+        }
+        t.split(&sep).map(|s| new_text(s.to_string())).collect()
+    } else {
+        t.split_whitespace().map(|s| new_text(s.to_string())).collect()
+    };
+    Ok(Block::List(parts))
+}
+
+pub fn _join(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_ge!(args, 1);
+    func_nargs_le!(args, 2);
+    let parts: Vec<String> = match &args[0] {
+        Block::List(l) => l.iter().map(to_text).collect(),
+        b => eval_error!(format!("join() argument 1: expected List, found {}", type_name(b)))
+    };
+    let sep: String = if args.len() == 2 { arg("join", args, 1)? } else { String::new() };
+    Ok(new_text(parts.join(&sep)))
+}
+
+fn _substring(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // substring(text, start) or substring(text, start, end), end is excluded
+    func_nargs_ge!(args, 2);
+    func_nargs_le!(args, 3);
+    let t: String = arg("substring", args, 0)?;
+    let start: usize = arg("substring", args, 1)?;
+    let end: usize = if args.len() == 3 { arg("substring", args, 2)? } else { t.chars().count() };
+    if start > end {
+        eval_error!(format!("substring() start {} is after end {}", start, end))
+    }
+    let from = _byte_index("substring", &t, start)?;
+    let to = _byte_index("substring", &t, end)?;
+    Ok(new_text(t[from..to].to_string()))
+}
+
+fn _format(_: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // `{}` takes the next argument, `{0}` a numbered one, `{{` and `}}` are literal braces
+    func_nargs_ge!(args, 1);
+    let fmt: String = arg("format", args, 0)?;
+    let values = &args[1..];
+    let mut out = String::new();
+    let mut next = 0;
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            },
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(d) => field.push(d),
+                        None => eval_error!("format() has an unclosed '{'")
+                    }
+                }
+                let idx = if field.is_empty() {
+                    next += 1;
+                    next - 1
+                } else {
+                    match field.trim().parse::<usize>() {
+                        Ok(i) => i,
+                        Err(_) => eval_error!(format!("format() invalid field '{{{}}}'", field))
+                    }
+                };
+                match values.get(idx) {
+                    Some(v) => out.push_str(&to_text(v)),
+                    None => eval_error!(format!("format() field {} out of range, {} values given", idx, values.len()))
+                }
+            },
+            '}' => eval_error!("format() has an unmatched '}'"),
+            _ => out.push(c),
+        }
+    }
+    Ok(new_text(out))
+}
+
+
+pub fn load_string(env_native: &mut ModuleVars) {
+    let mut m = Module::new(None);
+    let vars = &mut m.vars;
+
+    helper::load_func(vars, "split", _split);
+    helper::load_func(vars, "join", _join);
+    helper::load_func(vars, "substring", _substring);
+    helper::load_func(vars, "format", _format);
+
+    helper::load_typed(vars, "trim", _trim);
+    helper::load_typed(vars, "upper", _upper);
+    helper::load_typed(vars, "lower", _lower);
+    helper::load_typed(vars, "replace", _replace);
+    helper::load_typed(vars, "starts_with", _starts_with);
+    helper::load_typed(vars, "ends_with", _ends_with);
+    helper::load_typed(vars, "find", _find);
+    helper::load_typed(vars, "repeat", _repeat);
+    helper::load_typed(vars, "chars", _chars);
+    helper::load_typed(vars, "to_number", _to_number);
+    helper::load_typed(vars, "to_string", _to_string);
+
+    env_native.insert("string".to_string(), Block::Mod(m));
+}
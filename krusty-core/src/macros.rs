@@ -37,6 +37,22 @@ macro_rules! import_error {
 }
 
 
+#[macro_export]
+macro_rules! limit_error {
+    ($plain_string:expr) => {
+        return Err(Box::new(Error::LimitError{msg: $plain_string.to_string(), fname: String::from(""), lino: -1}))
+    };
+}
+
+
+#[macro_export]
+macro_rules! keyboard_interrupt_error {
+    () => {
+        return Err(Box::new(Error::KeyboardInterrupt{msg: "interrupted by user".to_string(), fname: String::from(""), lino: -1}))
+    };
+}
+
+
 #[macro_export]
 macro_rules! sys_exit_error {
     () => {
@@ -45,6 +61,23 @@ macro_rules! sys_exit_error {
 }
 
 
+#[macro_export]
+macro_rules! plugin_metadata {
+    // exports the `krusty_plugin_metadata` symbol checked by `Module::load_dylib` before calling `load_all`
+    ($name:expr) => {
+        #[no_mangle]
+        pub extern "C" fn krusty_plugin_metadata() -> *const $crate::lib::moddef::PluginMetadata {
+            static METADATA: $crate::lib::moddef::PluginMetadata = $crate::lib::moddef::PluginMetadata {
+                name: concat!($name, "\0").as_ptr() as *const ::std::os::raw::c_char,
+                version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const ::std::os::raw::c_char,
+                core_version: $crate::lib::moddef::CORE_VERSION.as_ptr() as *const ::std::os::raw::c_char,
+            };
+            &METADATA
+        }
+    };
+}
+
+
 #[macro_export]
 macro_rules! print_verbose {
     ($plain_string:expr) => {
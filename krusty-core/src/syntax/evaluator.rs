@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::cell::RefCell;
 use std::path::{Component, PathBuf};
 use path_slash::PathBufExt; // for PatjBuf::from_slash() trait
 
@@ -8,16 +9,32 @@ use std::env; // required for print_verbose! macro
 use super::parser::{Block, Expression};
 use super::lexer::Token;
 
-use crate::lib::{moddef::Module, builtins};
+use crate::lib::{funcdef::FuncDef, moddef::{Module, ModuleCache, ModuleVars}, streams::Streams, sandbox::Sandbox, builtins, pkg};
+use crate::lib::limits::{self, Limiter, Limits};
 use crate::lib::errors::{Error, KrustyErrorType};
 
 
+// result of running a function body
+// a tail call carries the variables of the frame it replaces, since the callee can still see them
+enum Flow {
+    Value(Block),
+    TailCall{func: Block, args: Vec<Block>, name: String, scope: ModuleVars},
+}
+
+const STACK_RED_ZONE: usize = 256 * 1024;
+const STACK_GROW_SIZE: usize = 4 * 1024 * 1024;
 
 
 #[derive(Debug)]
 pub struct NameSpace<'a> {
     builtin_funcs: Option<HashMap<String, Block>>,
+    modules: Option<RefCell<ModuleCache>>,
+    search_path: Option<RefCell<Vec<PathBuf>>>,
+    streams: Option<RefCell<Streams>>,
+    sandbox: Option<Sandbox>,
+    limiter: Option<RefCell<Limiter>>,
     parent: Option<&'a NameSpace<'a>>,
+    top: Option<&'a NameSpace<'a>>, // shortcut to the top of the stack, where root-only state is kept
     pub module: Module,
 }
 
@@ -25,14 +42,31 @@ pub struct NameSpace<'a> {
 impl<'a> NameSpace<'a> {
     pub fn new(path: Option<&PathBuf>, parent: Option<&'a NameSpace<'a>>) -> NameSpace<'a> {
         let mut builtin_funcs: Option<HashMap<String, Block>> = None;
+        let mut modules: Option<RefCell<ModuleCache>> = None;
+        let mut search_path: Option<RefCell<Vec<PathBuf>>> = None;
+        let mut streams: Option<RefCell<Streams>> = None;
+        let mut sandbox: Option<Sandbox> = None;
+        let mut limiter: Option<RefCell<Limiter>> = None;
+        let module = Module::new(path);
         if parent.is_none() {
             let mut b = HashMap::new();
             builtins::load_builtins(&mut b);
             builtin_funcs = Some(b);
+            modules = Some(RefCell::new(ModuleCache::default()));
+            search_path = Some(RefCell::new(pkg::default_search_path(module.path.as_ref())));
+            streams = Some(RefCell::new(Streams::default()));
+            sandbox = Some(Sandbox::default());
+            limiter = Some(RefCell::new(Limiter::new(Limits::default())));
         }
         NameSpace {
-            module: Module::new(path),
+            module,
             builtin_funcs,
+            modules,
+            search_path,
+            streams,
+            sandbox,
+            limiter,
+            top: parent.map(|p| p.root()),
             parent,
         }
     }
@@ -43,6 +77,9 @@ impl<'a> NameSpace<'a> {
 
     pub fn run(&mut self, elist: &Vec<Expression>) -> Result<Block, KrustyErrorType> {
         let mut return_val: Block = Block::Null;
+        if self.parent.is_none() {
+            self.get_limiter().borrow_mut().begin();
+        }
         for (_i, o) in elist.iter().enumerate() {
             return_val = self.solve_expr(o)?;
             if let Block::Operator(Token::FuncReturn) = o.op {
@@ -57,25 +94,31 @@ impl<'a> NameSpace<'a> {
     }
 
 
+    fn root(&self) -> &NameSpace<'a> {
+        match self.top {
+            Some(t) => t,
+            None => self,
+        }
+    }
+
     pub fn get(&self, key: &String) -> Result<Block, KrustyErrorType> {
-        match self.module.vars.get(key) {
+        let mut ns = self;
+        loop {
+            if let Some(v) = ns.module.vars.get(key) {
+                return Ok(v.clone())
+            }
+            match ns.parent {
+                Some(p) => ns = p,
+                None => break,
+            }
+        }
+        // No parent. Means we are at the top of the stack
+        // Search for builtins only at the top of the stack
+        match ns.builtin_funcs.as_ref().expect("no builtins?!").get(key) {
             Some(v) => Ok(v.clone()),
             None => {
-                match self.parent {
-                    Some(p) => p.get(key),
-                    None => {
-                        // No parent. Means we are at the top of the stack
-                        // Search for builtins only at the top of the stack
-                        match self.builtin_funcs.as_ref().expect("no builtins?!").get(key) {
-                            Some(v) => Ok(v.clone()),
-                            None => {
-                                eval_error!(format!("Symbol '{}' not found", key))
-                            }
-                        }
-                    }
-                }
-
-            },
+                eval_error!(format!("Symbol '{}' not found", key))
+            }
         }
     }
 
@@ -88,17 +131,72 @@ impl<'a> NameSpace<'a> {
     }
 
     fn set(&mut self, key: String, value: Block) -> Result<(), KrustyErrorType> {
+        let limiter = self.get_limiter();
+        if limiter.borrow().tracks_memory() {
+            let replaced = self.module.vars.get(&key).map_or(0, limits::approx_size);
+            limiter.borrow_mut().alloc(limits::approx_size(&value), replaced)?;
+        }
         self.module.vars.insert(key, value);
         Ok(())
     }
 
+    fn release_vars(&self) {
+        // variables of a finished scope no longer count towards the memory limit
+        let limiter = self.get_limiter();
+        if limiter.borrow().tracks_memory() {
+            let size = self.module.vars.values().map(limits::approx_size).sum();
+            limiter.borrow_mut().free(size);
+        }
+    }
+
+    pub fn builtins_mut(&mut self) -> &mut HashMap<String, Block> {
+        // host applications can register extra builtins on the top level namespace
+        self.builtin_funcs.as_mut().expect("builtins are only kept at the top of the stack")
+    }
+
+    pub fn get_module_cache(&self) -> &RefCell<ModuleCache> {
+        // imported modules are cached only at the top of the stack
+        self.root().modules.as_ref().expect("no module cache?!")
+    }
+
+    pub fn get_search_path(&self) -> &RefCell<Vec<PathBuf>> {
+        // module search path is shared by the whole stack, so it is kept at the top
+        self.root().search_path.as_ref().expect("no search path?!")
+    }
+
+    pub fn get_streams(&self) -> &RefCell<Streams> {
+        // print, eprint and input share the streams configured at the top of the stack
+        self.root().streams.as_ref().expect("no streams?!")
+    }
+
+    pub fn get_sandbox(&self) -> &Sandbox {
+        self.root().sandbox.as_ref().expect("no sandbox?!")
+    }
+
+    pub fn set_sandbox(&mut self, sandbox: Sandbox) {
+        // only the top level namespace can change the sandbox. Scripts never get mutable access to it
+        *self.sandbox.as_mut().expect("sandbox is only kept at the top of the stack") = sandbox;
+    }
+
+    pub fn get_limiter(&self) -> &RefCell<Limiter> {
+        self.root().limiter.as_ref().expect("no limiter?!")
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limiter.as_ref().expect("limits are only kept at the top of the stack").borrow_mut().set_limits(limits);
+    }
+
     pub fn get_path(&self) -> Option<PathBuf> {
-        if !self.module.path.is_none() { // path exists
-            self.module.path.clone()
-        } else if !self.parent.is_none() { // path doesn't exist, but parent exists
-            self.parent.unwrap().get_path() // get from parent
-        } else {
-            None
+        // nearest namespace with a path, usually the module being executed
+        let mut ns = self;
+        loop {
+            if ns.module.path.is_some() {
+                return ns.module.path.clone()
+            }
+            match ns.parent {
+                Some(p) => ns = p,
+                None => return None,
+            }
         }
     }
 
@@ -140,6 +238,7 @@ impl<'a> NameSpace<'a> {
                 // resolve ModBody to Mod
                 let mut ns = NameSpace::new(None, Some(self));
                 ns.run(&m)?;
+                ns.release_vars(); // counted again once the module is assigned
                 ns.to_block()
             },
             Block::FuncBody(_) => Ok(Block::Null), // this should never be called I think
@@ -167,6 +266,17 @@ impl<'a> NameSpace<'a> {
             Block::Expr(e) => {
                 match &e.elems[0] {
                     Block::Object(Token::Symbol(k)) => {
+                        if self.get_limiter().borrow().tracks_memory() {
+                            // the new item or member counts towards the memory limit in place of the one it replaces
+                            let replaced = match (self.module.vars.get(k), &e.op, &e.elems[1]) {
+                                (Some(Block::List(items)), Block::Operator(Token::Index), Block::Object(Token::Number(n))) =>
+                                    items.get(*n as usize).map_or(0, limits::approx_size),
+                                (Some(Block::Mod(m)), Block::Operator(Token::Accessor), Block::Object(Token::Symbol(prop))) =>
+                                    m.vars.get(prop).map_or(0, limits::approx_size),
+                                _ => 0,
+                            };
+                            self.get_limiter().borrow_mut().alloc(limits::approx_size(&val), replaced)?;
+                        }
                         let variable = self.get_mut(&k)?; // get mutable reference to variable so it can be modified inplace
                         match (variable, &e.op, &e.elems[1]) {
                             (l, Block::Operator(Token::Index), Block::Object(Token::Number(n))) => {
@@ -241,40 +351,111 @@ impl<'a> NameSpace<'a> {
     }
 
     pub fn eval_func_obj(&mut self, func: &Block, args: &Block, name: Option<&String>) -> Result<Block, KrustyErrorType> {
-        let name = match name {
-            Some(s) => s,
-            None => "anonymous"
+        let mut name = match name {
+            Some(s) => s.to_string(),
+            None => "anonymous".to_string()
         };
 
-        let args: Vec<Block> = match args {
+        let mut args: Vec<Block> = match args {
             Block::List(l) => l.to_vec(),
             _ => vec![args.clone()]
         };
 
-        match func {
-            Block::Func(f) => {
-                let req_args = f.args.get_list().unwrap_or(vec![]); //.expect("function definition error");
-                if req_args.len() != args.len() {
-                    eval_error!(format!("function arguments for '{}' don't match", name));
-                } else {
-                    match &f.body { // return function result
-                        Block::FuncBody(elist) => {
-                            let mut exec_env = NameSpace::new(None, Some(self));
-                            print_verbose!("CALL {} {:?}", name, f.body);
-                            for (k,v) in req_args.iter().zip(args.iter()) {
-                                exec_env.assign(&k, &v)?;
-                            }
-                            exec_env.run(&elist)
+        // tail calls hand the next function back here instead of nesting, so they run in constant stack space
+        let mut func = func.clone();
+        let mut scope = ModuleVars::new();
+        loop {
+            let flow = match &func {
+                Block::Func(f) => self.call_func_def(f, &args, &name, scope)?,
+                Block::NativeFunc(f) => {
+                    let clean_args: Vec<Block> = self.resolve_vector(&args)?;
+                    return f.call(self, &clean_args)
+                }
+                _ => eval_error!(format!("Function '{}' definition error", name))
+            };
+            match flow {
+                Flow::Value(v) => return Ok(v),
+                Flow::TailCall{func: f, args: a, name: n, scope: v} => {
+                    func = f;
+                    args = a;
+                    name = n;
+                    scope = v;
+                }
+            }
+        }
+    }
+
+    fn call_func_def(&mut self, f: &FuncDef, args: &[Block], name: &str, scope: ModuleVars) -> Result<Flow, KrustyErrorType> {
+        let req_args = f.args.get_list().unwrap_or(vec![]); //.expect("function definition error");
+        if req_args.len() != args.len() {
+            eval_error!(format!("function arguments for '{}' don't match", name));
+        }
+        match &f.body { // return function result
+            Block::FuncBody(elist) => {
+                self.get_limiter().borrow_mut().enter_call()?;
+                // deep recursion continues on a heap allocated stack instead of overflowing
+                let res = stacker::maybe_grow(STACK_RED_ZONE, STACK_GROW_SIZE, || {
+                    let mut exec_env = NameSpace::new(None, Some(self));
+                    exec_env.module.vars = scope;
+                    print_verbose!("CALL {} {:?}", name, f.body);
+                    for (k,v) in req_args.iter().zip(args.iter()) {
+                        exec_env.assign(&k, &v)?;
+                    }
+                    match exec_env.run_func_body(&elist) {
+                        Ok(Flow::TailCall{func, args, name, ..}) => {
+                            // still counted towards the memory limit, the next frame takes them over
+                            Ok(Flow::TailCall{func, args, name, scope: exec_env.module.vars})
                         },
-                        _ => eval_error!(format!("Function '{}' definition error", name)),
+                        res => {
+                            exec_env.release_vars();
+                            res
+                        }
                     }
-                }
+                });
+                self.get_limiter().borrow_mut().exit_call();
+                res
             },
-            Block::NativeFunc(f) => {
-                let clean_args: Vec<Block> = self.resolve_vector(&args)?;
-                (f.func)(self, &clean_args)
+            _ => eval_error!(format!("Function '{}' definition error", name)),
+        }
+    }
+
+    fn run_func_body(&mut self, elist: &[Expression]) -> Result<Flow, KrustyErrorType> {
+        // same as `run`, except that `ret f(...)` returns the call to be made instead of making it
+        let mut return_val: Block = Block::Null;
+        for o in elist.iter() {
+            if let Block::Operator(Token::FuncReturn) = o.op {
+                if let [Block::Expr(call)] = o.elems.as_slice() {
+                    if let Block::Operator(Token::FuncCall) = call.op {
+                        self.get_limiter().borrow_mut().step()?;
+                        return self.prepare_tail_call(call)
+                    }
+                }
+                return Ok(Flow::Value(self.solve_expr(o)?))
             }
-            _ => eval_error!(format!("Function '{}' definition error", name))
+            return_val = self.solve_expr(o)?;
+        }
+        Ok(Flow::Value(return_val))
+    }
+
+    fn prepare_tail_call(&mut self, call: &Expression) -> Result<Flow, KrustyErrorType> {
+        // the callee and its arguments are evaluated here, before this frame is discarded
+        let (func, name) = match &call.elems[0] {
+            Block::Object(Token::Symbol(func_name)) => match self.get(func_name) {
+                Ok(f) => (f, func_name.to_string()),
+                Err(_) => eval_error!(format!("Function '{}' not defined", func_name)),
+            },
+            Block::Func(_) => (call.elems[0].clone(), "anonymous".to_string()),
+            Block::Expr(ex) => (self.solve_expr(ex)?, "anonymous".to_string()),
+            _ => return Ok(Flow::Value(Block::Null)),
+        };
+        let args = match &call.elems[1] {
+            Block::List(l) => self.resolve_vector(l)?,
+            a => vec![self.resolve(a)?],
+        };
+        match func {
+            // native functions may call back into krusty code that expects this frame's variables
+            Block::NativeFunc(_) => Ok(Flow::Value(self.eval_func_obj(&func, &Block::List(args), Some(&name))?)),
+            _ => Ok(Flow::TailCall{func, args, name, scope: ModuleVars::new()}),
         }
     }
 
@@ -315,6 +496,7 @@ impl<'a> NameSpace<'a> {
 
     fn solve_expr(&mut self, exp: &Expression) -> Result<Block, KrustyErrorType> {
         // println!("<E> {:?}", exp);
+        self.get_limiter().borrow_mut().step()?;
         match &exp.op {
             Block::Operator(Token::Assign) => {
                 // elems should have only 2 members
@@ -390,6 +572,9 @@ impl<'a> NameSpace<'a> {
                     Some(m) => {
                         match &exp.elems[1] {
                             Block::Object(Token::Symbol(s)) => {
+                                if !m.is_public(s) {
+                                    eval_error!(format!("member '{}' is private", s))
+                                }
                                 let var = m.vars.get(s);
                                 if var.is_some() {Ok(var.unwrap().clone())} else {eval_error!("member not found")}
                             },
@@ -397,9 +582,20 @@ impl<'a> NameSpace<'a> {
                                 match x.op {
                                     Block::Operator(Token::Assign) => eval_error!("cannot assign into module"),
                                     _ => {
+                                        if let Some(Block::Object(Token::Symbol(s))) = x.elems.first() {
+                                            if !m.is_public(s) {
+                                                eval_error!(format!("member '{}' is private", s))
+                                            }
+                                        }
                                         let mut ns = NameSpace { // create new execution namespace
                                             builtin_funcs: None,
+                                            modules: None,
+                                            search_path: None,
+                                            streams: None,
+                                            sandbox: None,
+                                            limiter: None,
                                             module: m.clone(),
+                                            top: Some(self.root()),
                                             parent: Some(self)
                                         };
                                         ns.solve_expr(x)
@@ -3,7 +3,7 @@ use std::fmt;
 #[cfg(debug_assertions)]
 use std::env; // required for print_verbose! macro
 
-use crate::lib::{funcdef, moddef};
+use crate::lib::{convert, funcdef, moddef};
 use crate::lib::errors::{Error, KrustyErrorType};
 
 use super::lexer;
@@ -23,6 +23,7 @@ pub enum Block {
     NativeFunc(funcdef::NativeFuncDef),
     Mod(moddef::Module),
     ModBody(Vec<Expression>), // same definition as FuncBody, but evaluated differently
+    Type(convert::TypeValue),
 }
 
 
@@ -44,6 +45,7 @@ impl fmt::Display for Block {
                 write!(f, ")")
             },
             Block::Mod(m) => write!(f, "<module at {:p}>", m),
+            Block::Type(t) => write!(f, "{}", t),
             _ => write!(f, "{:?}", self),
         }
     }
@@ -1,20 +1,20 @@
 use std::path::PathBuf;
+use std::time::Duration;
 use path_slash::PathBufExt; // for PatjBuf::from_slash() trait
 use std::env; // required for print_verbose! macro
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 
 #[macro_use] extern crate krusty_repl;
 use krusty_repl::prompt;
 
 #[macro_use] extern crate krusty_core;
-use krusty_core::syntax::lexer;
 use krusty_core::syntax::parser;
-use krusty_core::syntax::evaluator;
 use krusty_core::lib::errors::{Error, KrustyErrorType};
+use krusty_core::Interpreter;
 
-use krusty_core::lib::pkg;
+use krusty_core::lib::{pkg, archive, sandbox::Sandbox, limits::{self, InterruptHandle, Limits}};
 
 
 pub const APP_NAME_STR: &'static str = env!("CARGO_PKG_NAME");
@@ -23,6 +23,8 @@ pub const VERSION_STR: &'static str = env!("CARGO_PKG_VERSION");
 const REPL_HISTFILE: &'static str = "history.txt";
 const REPL_HISTLEN: usize = 20;
 
+const SIGINT_EXIT_CODE: i32 = 130;
+
 fn is_sysexit(err: &KrustyErrorType) -> bool {
     match err.as_any().downcast_ref::<Error>() {
         Some(Error::SysExit{..}) => true,
@@ -31,10 +33,23 @@ fn is_sysexit(err: &KrustyErrorType) -> bool {
 }
 
 
-fn repl_run_line(ns: &mut evaluator::NameSpace, buf: &String) -> Result<parser::Block, KrustyErrorType> {
-    let mut tokens = lexer::lex(&buf)?;
-    let parsed = parser::parse(&mut tokens)?;
-    let blk = ns.run(&parsed)?;
+fn install_interrupt_handler(handle: InterruptHandle) {
+    // the first Ctrl+C raises a KeyboardInterrupt in the running script,
+    // a second one while that is still pending (e.g. blocked in a native call) kills the process
+    let res = ctrlc::set_handler(move || {
+        if handle.is_interrupted() {
+            std::process::exit(SIGINT_EXIT_CODE);
+        }
+        handle.interrupt();
+    });
+    if let Err(e) = res {
+        print_verbose!("Unable to install Ctrl+C handler: {}", e);
+    }
+}
+
+
+fn repl_run_line(interp: &mut Interpreter, buf: &str) -> Result<parser::Block, KrustyErrorType> {
+    let blk = interp.eval_str(buf)?;
     match blk {
         parser::Block::Null => (),
         _ => println!("{}", blk)
@@ -43,7 +58,33 @@ fn repl_run_line(ns: &mut evaluator::NameSpace, buf: &String) -> Result<parser::
 }
 
 
-fn repl_prompt() -> Result<(), KrustyErrorType> {
+fn configure_interpreter(interp: &mut Interpreter, cli: &CliOpts, interrupt: &InterruptHandle) {
+    interp.set_interrupt_handle(interrupt.clone());
+    // directories given on the command line are searched before the defaults
+    for d in cli.path.iter().rev() {
+        interp.add_search_path(PathBuf::from_slash(d));
+    }
+    interp.set_limits(Limits {
+        max_depth: match cli.max_depth {
+            Some(0) => None,
+            Some(d) => Some(d),
+            None => Some(limits::DEFAULT_MAX_DEPTH),
+        },
+        max_steps: cli.max_steps,
+        timeout: cli.timeout,
+        max_memory: cli.max_memory,
+    });
+    if cli.sandbox {
+        interp.set_sandbox(Sandbox {
+            enabled: true,
+            import_roots: cli.allow_import.iter().map(PathBuf::from_slash).collect(),
+            native_modules: cli.allow_native.iter().map(PathBuf::from_slash).collect(),
+        });
+    }
+}
+
+
+fn repl_prompt(cli: &CliOpts, interrupt: &InterruptHandle) -> Result<(), KrustyErrorType> {
     println!(
         "{} {} {} {}",
         GREEN!("Welcome to Krusty"),
@@ -51,7 +92,8 @@ fn repl_prompt() -> Result<(), KrustyErrorType> {
         GREEN!("repl. Ctrl+C or exit() to quit!")
     );
     let cwd = env::current_dir().unwrap_or(PathBuf::from("."));
-    let mut ns = evaluator::NameSpace::new(Some(&cwd), None);
+    let mut interp = Interpreter::with_root(&cwd)?;
+    configure_interpreter(&mut interp, cli, interrupt);
 
     let mut cli_hist_file = pkg::get_install_path().unwrap_or(PathBuf::from("."));
     cli_hist_file.push(REPL_HISTFILE);
@@ -65,7 +107,8 @@ fn repl_prompt() -> Result<(), KrustyErrorType> {
                 match buffer {
                     Ok(buf) if buf.trim().len() == 0 => (),
                     Ok(buf) => {
-                        match repl_run_line(&mut ns, &buf) {
+                        interrupt.clear(); // drop a Ctrl+C that arrived after the previous line finished
+                        match repl_run_line(&mut interp, &buf) {
                             Ok(_) => (),
                             Err(e) => {
                                 if is_sysexit(&e) {
@@ -90,15 +133,14 @@ fn repl_prompt() -> Result<(), KrustyErrorType> {
 }
 
 
-fn run_file(filepath: &PathBuf) -> Result<(), KrustyErrorType> {
-    let mut ns = evaluator::NameSpace::new(Some(filepath), None);
-    print_verbose!("Running {:?}", ns.get_path());
+fn run_file(filepath: &PathBuf, cli: &CliOpts, interrupt: &InterruptHandle) -> Result<(), KrustyErrorType> {
+    let mut interp = Interpreter::with_root(filepath)?;
+    configure_interpreter(&mut interp, cli, interrupt);
+    print_verbose!("Running {:?}", filepath);
 
-    let mut tokens = lexer::lex_file(filepath)?;
-    let tree = parser::parse(&mut tokens)?;
-    let _vo = ns.run(&tree)?;
+    let _vo = interp.eval_file(filepath)?;
 
-    print_verbose!("FINAL\n{:?}\n{:?}", _vo, ns);
+    print_verbose!("FINAL\n{:?}\n{:?}", _vo, interp.namespace());
     Ok(())
 }
 
@@ -108,6 +150,16 @@ fn run_file(filepath: &PathBuf) -> Result<(), KrustyErrorType> {
 
 
 
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    // Duration::from_secs_f64 panics on nan, negative and huge values
+    let secs: f64 = s.parse().map_err(|_| format!("'{}' is not a number", s))?;
+    if !secs.is_finite() || secs <= 0.0 {
+        return Err(format!("expected a positive number of seconds, found {}", s));
+    }
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("{} seconds is too long", s))
+}
+
+
 /// Experimental programing language written in Rust
 #[derive(Parser)]
 #[clap(name = APP_NAME_STR)]
@@ -119,6 +171,90 @@ pub struct CliOpts {
     /// package paths to install
     #[clap(short, long, name = "packages")]
     pub install: Option<Vec<String>>,
+
+    /// local package registry used to resolve dependencies while installing
+    #[clap(short, long, name = "registry")]
+    pub registry: Option<String>,
+
+    /// additional module search directories
+    #[clap(short, long, name = "dirs", number_of_values = 1)]
+    pub path: Vec<String>,
+
+    /// run scripts without native modules, and with imports limited to --allow-import directories
+    #[clap(long)]
+    pub sandbox: bool,
+
+    /// directories the sandbox allows importing modules from
+    #[clap(long, name = "import_dirs", number_of_values = 1)]
+    pub allow_import: Vec<String>,
+
+    /// native libraries the sandbox allows loading with import_native
+    #[clap(long, name = "libs", number_of_values = 1)]
+    pub allow_native: Vec<String>,
+
+    /// maximum depth of nested function calls, 0 for no limit
+    #[clap(long, name = "depth")]
+    pub max_depth: Option<usize>,
+
+    /// maximum number of expressions a script may evaluate
+    #[clap(long, name = "steps")]
+    pub max_steps: Option<u64>,
+
+    /// maximum run time of a script in seconds
+    #[clap(long, name = "seconds", parse(try_from_str = parse_timeout))]
+    pub timeout: Option<Duration>,
+
+    /// approximate maximum bytes held by script variables
+    #[clap(long, name = "bytes")]
+    pub max_memory: Option<usize>,
+
+    /// overwrite or remove packages even if they are installed or required
+    #[clap(short, long, global = true)]
+    pub force: bool,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// manage installed packages
+    Pkg {
+        #[clap(subcommand)]
+        action: PkgAction,
+    },
+}
+
+
+#[derive(Subcommand)]
+pub enum PkgAction {
+    /// list installed packages
+    List,
+    /// show details of an installed package
+    Info { package: String },
+    /// remove an installed package
+    Uninstall { package: String },
+    /// reinstall packages that have a newer version at their source
+    Upgrade { package: Option<String> },
+    /// create an archive of a package directory that can be passed to --install, with a sha256 file to detect corrupted copies
+    Pack { dir: String },
+}
+
+
+fn run_pkg_command(action: &PkgAction, opts: &pkg::InstallOptions) -> Result<(), KrustyErrorType> {
+    match action {
+        PkgAction::List => pkg::list_pkgs(),
+        PkgAction::Info{package} => pkg::pkg_info(package),
+        PkgAction::Uninstall{package} => pkg::uninstall_pkg(package, opts),
+        PkgAction::Upgrade{package} => pkg::upgrade_pkgs(package.as_ref(), opts),
+        PkgAction::Pack{dir} => {
+            let cwd = env::current_dir().unwrap_or(PathBuf::from("."));
+            let archive_path = archive::pack_pkg(&PathBuf::from_slash(dir), &cwd)?;
+            println!("Created {:?}", archive_path);
+            Ok(())
+        },
+    }
 }
 
 
@@ -126,12 +262,24 @@ pub struct CliOpts {
 fn main() -> Result<(), i8> {
     let mut success: bool = true;
     let cli = CliOpts::parse();
-
-    if cli.scripts.len() > 0 {
+    let opts = pkg::InstallOptions {
+        registry: cli.registry.as_ref().map(PathBuf::from_slash),
+        force: cli.force,
+    };
+
+    if let Some(Command::Pkg{action}) = &cli.command {
+        if let Err(e) = run_pkg_command(action, &opts) {
+            println!("{:?}", e.msg());
+            return Err(1)
+        }
+    }
+    else if cli.scripts.len() > 0 {
+        let interrupt = InterruptHandle::new();
+        install_interrupt_handler(interrupt.clone());
         for f in &cli.scripts {
             let filepath = PathBuf::from_slash(f);
             if filepath.is_file() {
-                match run_file(&filepath) {
+                match run_file(&filepath, &cli, &interrupt) {
                     Ok(_) => (),
                     Err(e) => {
                         if !is_sysexit(&e) {
@@ -146,14 +294,16 @@ fn main() -> Result<(), i8> {
     }
     else if cli.install.is_some() {
         for f in &cli.install.unwrap() {
-            if let Err(e) = pkg::install_pkg(f) {
+            if let Err(e) = pkg::install_pkg(f, &opts) {
                 println!("{:?}", e.msg());
                 return Err(1)
             }
         }
     }
     else {
-        let res = repl_prompt();
+        let interrupt = InterruptHandle::new();
+        install_interrupt_handler(interrupt.clone());
+        let res = repl_prompt(&cli, &interrupt);
         if res.is_err() {
             success = false;
         }
@@ -15,12 +15,17 @@ use krusty_core::lib::helper;
 
 
 fn create_filemodule(filepath: &String) -> Module {
+	// each method captures the path of the file it was created for
 	let mut fobj = Module::new(None);
 	fobj.vars.insert("filepath".to_string(), Block::Object(Token::Text(filepath.clone())));
-	helper::load_func(&mut fobj.vars, "read", _read);
-	helper::load_func(&mut fobj.vars, "read_all", _read_all);
-	helper::load_func(&mut fobj.vars, "write", _write);
-	helper::load_func(&mut fobj.vars, "append", _append);
+	let f = filepath.clone();
+	helper::load_closure(&mut fobj.vars, "read", move |_ns, args| _read(&f, args));
+	let f = filepath.clone();
+	helper::load_closure(&mut fobj.vars, "read_all", move |_ns, args| _read_all(&f, args));
+	let f = filepath.clone();
+	helper::load_closure(&mut fobj.vars, "write", move |_ns, args| _write(&f, args));
+	let f = filepath.clone();
+	helper::load_closure(&mut fobj.vars, "append", move |_ns, args| _append(&f, args));
 	fobj
 }
 
@@ -55,25 +60,18 @@ pub fn _filecreate(_ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, Krus
 	}
 }
 
-fn _read_all(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+fn _read_all(f: &str, args: &[Block]) -> Result<Block, KrustyErrorType> {
     func_nargs_eq!(args, 0); // 0 args
-	let fpath = ns.get(&"filepath".to_string())?;
-	match fpath {
-		Block::Object(Token::Text(f)) => {
-			let mut file = OpenOptions::new().read(true).open(f)?;
-			let mut contents = String::new();
-			file.read_to_string(&mut contents)?;
-			Ok(Block::Object(Token::Text(contents)))
-		},
-		_ => eval_error!("File read error")
-	}
+	let mut file = OpenOptions::new().read(true).open(f)?;
+	let mut contents = String::new();
+	file.read_to_string(&mut contents)?;
+	Ok(Block::Object(Token::Text(contents)))
 }
 
-fn _read(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
-    func_nargs_eq!(args, 1); // 0 args
-	let fpath = ns.get(&"filepath".to_string())?;
-	match (fpath, &args[0]) {
-		(Block::Object(Token::Text(f)), Block::Object(Token::Number(n))) => {
+fn _read(f: &str, args: &[Block]) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1); // 1 args
+	match &args[0] {
+		Block::Object(Token::Number(n)) => {
 			let file = OpenOptions::new().read(true).open(f)?;
 			let mut buf = BufReader::with_capacity(*n as usize, file);
 			let contents = String::from_utf8_lossy(buf.fill_buf()?).into_owned();
@@ -84,11 +82,10 @@ fn _read(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType
 }
 
 
-fn _write(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
-    func_nargs_eq!(args, 1); // 0 args
-	let fpath = ns.get(&"filepath".to_string())?;
-	match (fpath, &args[0]) {
-		(Block::Object(Token::Text(f)), Block::Object(Token::Text(t))) => {
+fn _write(f: &str, args: &[Block]) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1); // 1 args
+	match &args[0] {
+		Block::Object(Token::Text(t)) => {
 			let file = OpenOptions::new().write(true).open(f)?;
 			let mut buffer = BufWriter::new(file);
 			buffer.write_all(t.as_bytes())?;
@@ -100,11 +97,10 @@ fn _write(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorTyp
 }
 
 
-fn _append(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
-    func_nargs_eq!(args, 1); // 0 args
-	let fpath = ns.get(&"filepath".to_string())?;
-	match (fpath, &args[0]) {
-		(Block::Object(Token::Text(f)), Block::Object(Token::Text(t))) => {
+fn _append(f: &str, args: &[Block]) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1); // 1 args
+	match &args[0] {
+		Block::Object(Token::Text(t)) => {
 			let file = OpenOptions::new().append(true).open(f)?;
 			let mut buffer = BufWriter::new(file);
 			buffer.write_all(t.as_bytes())?;
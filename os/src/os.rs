@@ -44,22 +44,12 @@ pub fn _listdir(_ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyE
     }
 }
 
-pub fn _getcwd(_ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
-    func_nargs_eq!(args, 0); // 0 args
+pub fn _getcwd() -> Result<String, KrustyErrorType> {
     let cwd = env::current_dir().unwrap_or(PathBuf::from("."));
-    let cwd = fs::canonicalize(&cwd)?.to_str().ok_or("Something went wrong")?.to_string();
-    Ok(Block::Object(Token::Text(cwd)))
+    Ok(fs::canonicalize(&cwd)?.to_str().ok_or("Something went wrong")?.to_string())
 }
 
-pub fn _remove(_ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
-    func_nargs_eq!(args, 1); // 1 args
-    match &args[0] {
-        Block::Object(Token::Text(t)) => {
-            fs::remove_file(t)?;
-        },
-        _ => ()
-    }
-    Ok(Block::Null)
+pub fn _remove(path: String) -> Result<(), KrustyErrorType> {
+    fs::remove_file(path)?;
+    Ok(())
 }
-
-
@@ -1,3 +1,5 @@
+MODE="$1"
+
 if [ "$1" = "binary" ]; then
     cargo build || exit 1
 fi
@@ -16,5 +18,135 @@ for f in ./test_code/*.krt; do
     # sleep 1
 done
 
+# scripts in subfolders need extra options
+SANDBOX_OPTS="--sandbox --allow-import test_code/imports --allow-native target/debug/os"
+echo "RUNNING ./test_code/sandbox/test_sandbox.krt"
+if [ "$1" = "binary" ]; then
+    target/debug/krusty $SANDBOX_OPTS test_code/sandbox/test_sandbox.krt || exit 1
+else
+    cargo run -- $SANDBOX_OPTS test_code/sandbox/test_sandbox.krt || exit 1
+fi
+
+# the C plugin example is compiled separately
+echo "RUNNING ./test_code/native/test_c_plugin.krt"
+make -s c_plugin || exit 1
+if [ "$1" = "binary" ]; then
+    target/debug/krusty test_code/native/test_c_plugin.krt || exit 1
+else
+    cargo run test_code/native/test_c_plugin.krt || exit 1
+fi
+
+# input() reads from stdin
+echo "RUNNING ./test_code/io/read_input.krt"
+if [ "$1" = "binary" ]; then
+    out=$(printf 'krusty\r\nsecond line' | target/debug/krusty test_code/io/read_input.krt)
+else
+    out=$(printf 'krusty\r\nsecond line' | cargo run -q test_code/io/read_input.krt)
+fi
+if [ $? -ne 0 ] || [ "$out" != "name? input ok" ]; then
+    echo "$out"
+    exit 1
+fi
+
+# the embedding example checks its own results
+echo "RUNNING krusty-core/examples/embed.rs"
+cargo run -q -p krusty-core --example embed || exit 1
+
+# scripts that must stop with a specific error
+expect_error() {
+    expected="$1"
+    shift
+    echo "RUNNING krusty $* (expecting \"$expected\")"
+    if [ "$MODE" = "binary" ]; then
+        out=$(target/debug/krusty "$@" 2>&1)
+    else
+        out=$(cargo run -q -- "$@" 2>&1)
+    fi
+    status=$?
+    if [ $status -eq 0 ] || ! echo "$out" | grep -qF "$expected"; then
+        echo "$out"
+        echo "expected a failure reporting: $expected"
+        exit 1
+    fi
+}
+
+expect_error "sandbox: native module" $SANDBOX_OPTS test_code/sandbox/refused_native.krt
+expect_error "sandbox: importing" $SANDBOX_OPTS test_code/sandbox/refused_import.krt
+expect_error "step budget of 1000 exceeded" --max-steps 1000 test_code/limits/spin.krt
+expect_error "timeout: execution took longer than 200ms" --timeout 0.2 test_code/limits/spin.krt
+expect_error "memory limit of 100000 bytes exceeded" --max-memory 100000 test_code/limits/grow.krt
+expect_error "memory limit of 100000 bytes exceeded" --max-memory 100000 test_code/limits/grow_item.krt
+expect_error "memory limit of 100000 bytes exceeded" --max-memory 100000 test_code/limits/grow_member.krt
+expect_error "expected a positive number of seconds" --timeout nan test_code/test.krt
+expect_error "expected a positive number of seconds" --timeout=-1 test_code/test.krt
+expect_error "seconds is too long" --timeout 1e30 test_code/test.krt
+
+# scripts and commands that must succeed and print a specific line
+expect_output() {
+    expected="$1"
+    shift
+    echo "RUNNING krusty $* (expecting \"$expected\")"
+    out=$(target/debug/krusty "$@" 2>&1)
+    status=$?
+    if [ $status -ne 0 ] || ! echo "$out" | grep -qF "$expected"; then
+        echo "$out"
+        echo "expected success printing: $expected"
+        exit 1
+    fi
+}
+
+# package manager, run against a temporary HOME so that ~/.krusty is left alone
+if [ "$MODE" != "binary" ]; then
+    cargo build || exit 1
+    MODE="binary"
+fi
+PKG_HOME=$(mktemp -d)
+trap 'rm -rf "$PKG_HOME"' EXIT
+export HOME="$PKG_HOME"
+PKGS=test_code/packages
+INSTALLED="$PKG_HOME/.krusty/pkg"
+
+# manifests, dependency resolution and the lockfile
+expect_output "lock: " --install $PKGS/app --registry $PKGS/registry
+if ! grep -qF 'version = "0.3.1"' $INSTALLED/app/krusty.lock || [ -e $PKGS/app/krusty.lock ]; then
+    echo "the lockfile must be written to the installed package, not its source"
+    exit 1
+fi
+expect_output "passed installed packages" $PKGS/scripts/use_app.krt
+expect_error "'units' is already installed (0.3.1)" --install $PKGS/pinned --registry $PKGS/registry
+expect_output "Installing dependency units 0.3.0" --install $PKGS/pinned --registry $PKGS/registry --force
+expect_error "must be a relative path inside the package" --install $PKGS/bad_entry
+expect_error "broken/krusty.toml: expected a value" $PKGS/scripts/import_broken.krt
+
+# list, info, upgrade and uninstall the packages installed above
+expect_output "units 0.3.0" pkg list
+expect_output "required by: app, pinned" pkg info units
+expect_output "Upgrading units 0.3.0 -> 0.3.1" pkg upgrade
+expect_output "version: 0.3.1" pkg info units
+mkdir "$PKG_HOME/src" && cp -r $PKGS/trig "$PKG_HOME/src/" || exit 1
+expect_output "Package trig 1.2.0" --install "$PKG_HOME/src/trig" --force
+sed 's/1.2.0/1.3.0/' $PKGS/trig/krusty.toml > "$PKG_HOME/src/trig/krusty.toml"
+expect_output "Upgrading trig 1.2.0 -> 1.3.0" pkg upgrade trig
+expect_error "'units' is required by app, pinned" pkg uninstall units
+expect_output "Uninstalling pinned 0.1.0" pkg uninstall pinned
+expect_output "Uninstalling app 0.1.0" pkg uninstall app
+expect_output "Uninstalling units 0.3.1" pkg uninstall units
+expect_error "'units' is not installed" pkg info units
+expect_output "trig 1.3.0" pkg list
+if [ -e $INSTALLED/units ] || [ -e $INSTALLED/app ]; then
+    echo "uninstall must remove the package files"
+    exit 1
+fi
+
+# pack, install the archive, and refuse it once it no longer matches its checksum
+KRUSTY="$PWD/target/debug/krusty"
+(cd "$PKG_HOME/src" && "$KRUSTY" pkg pack trig) || exit 1
+ARCHIVE="$PKG_HOME/src/trig-1.3.0.tar.gz"
+expect_output "checksum matches" --install "$ARCHIVE" --force
+echo "0000000000000000000000000000000000000000000000000000000000000000  trig-1.3.0.tar.gz" > "$ARCHIVE.sha256"
+expect_error "checksum mismatch" --install "$ARCHIVE" --force
+rm "$ARCHIVE.sha256"
+expect_error "checksum file" --install "$ARCHIVE" --force
+
 echo "success!!!"
 exit 0
@@ -0,0 +1,87 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use krusty_core::syntax::evaluator::NameSpace;
+use krusty_core::syntax::parser::Block;
+
+use krusty_core::lib::moddef::Module;
+use krusty_core::lib::convert::{arg, IntoKrusty};
+use krusty_core::lib::errors::{Error, KrustyErrorType};
+use krusty_core::lib::helper;
+
+
+// sleeps are split up so that Ctrl+C does not have to wait for them to finish
+const SLEEP_SLICE: Duration = Duration::from_millis(50);
+
+static MONOTONIC_START: OnceLock<Instant> = OnceLock::new();
+
+
+pub fn _now() -> Result<f64, KrustyErrorType> {
+    // seconds since the unix epoch, with fractions
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => Ok(d.as_secs_f64()),
+        Err(_) => eval_error!("now() system clock is set before 1970")
+    }
+}
+
+pub fn _monotonic() -> Result<f64, KrustyErrorType> {
+    // seconds since the module was first used, never goes backwards
+    Ok(MONOTONIC_START.get_or_init(Instant::now).elapsed().as_secs_f64())
+}
+
+pub fn _sleep(ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    func_nargs_eq!(args, 1);
+    let secs: f64 = arg("sleep", args, 0)?;
+    if !secs.is_finite() || secs < 0.0 {
+        eval_error!(format!("sleep() expected a non-negative number of seconds, found {}", secs))
+    }
+    // Duration::from_secs_f64 and adding to an Instant panic on values that are too large
+    let until = match Duration::try_from_secs_f64(secs).ok().and_then(|d| Instant::now().checked_add(d)) {
+        Some(t) => t,
+        None => eval_error!(format!("sleep() {} seconds is too long", secs))
+    };
+    let interrupt = ns.get_limiter().borrow().interrupt_handle();
+    loop {
+        let now = Instant::now();
+        if now >= until || interrupt.is_interrupted() {
+            // a pending interrupt is raised by the evaluator at its next step
+            break;
+        }
+        thread::sleep((until - now).min(SLEEP_SLICE));
+    }
+    Ok(Block::Null)
+}
+
+
+pub fn _stopwatch() -> Result<Block, KrustyErrorType> {
+    // sw = time.stopwatch(); ... sw.elapsed()
+    let mut sw = Module::new(None);
+    let started = Rc::new(Cell::new(Instant::now()));
+    let lap = Rc::new(Cell::new(Instant::now()));
+
+    let s = started.clone();
+    helper::load_closure(&mut sw.vars, "elapsed", move |_ns, args| {
+        func_nargs_eq!(args, 0);
+        Ok(s.get().elapsed().as_secs_f64().into_krusty())
+    });
+    let (s, l) = (started.clone(), lap.clone());
+    helper::load_closure(&mut sw.vars, "reset", move |_ns, args| {
+        // returns the time elapsed before the reset
+        func_nargs_eq!(args, 0);
+        let elapsed = s.get().elapsed().as_secs_f64();
+        s.set(Instant::now());
+        l.set(Instant::now());
+        Ok(elapsed.into_krusty())
+    });
+    helper::load_closure(&mut sw.vars, "lap", move |_ns, args| {
+        // time since the previous lap, or since the start
+        func_nargs_eq!(args, 0);
+        let elapsed = lap.get().elapsed().as_secs_f64();
+        lap.set(Instant::now());
+        Ok(elapsed.into_krusty())
+    });
+    Ok(Block::Mod(sw))
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono::format::{Item, StrftimeItems};
+
+use krusty_core::syntax::evaluator::NameSpace;
+use krusty_core::syntax::lexer::Token;
+use krusty_core::syntax::parser::Block;
+
+use krusty_core::lib::convert::arg;
+use krusty_core::lib::errors::{Error, KrustyErrorType};
+
+
+// format and parse work in UTC unless "local" is passed as the last argument
+enum Zone {
+    Utc,
+    Local,
+}
+
+fn _zone(func: &str, args: &[Block], idx: usize) -> Result<Zone, KrustyErrorType> {
+    if args.len() <= idx {
+        return Ok(Zone::Utc);
+    }
+    let zone: String = arg(func, args, idx)?;
+    match zone.as_str() {
+        "utc" => Ok(Zone::Utc),
+        "local" => Ok(Zone::Local),
+        _ => eval_error!(format!("{}() unknown time zone '{}', expected 'utc' or 'local'", func, zone))
+    }
+}
+
+fn _items<'f>(func: &str, fmt: &'f str) -> Result<Vec<Item<'f>>, KrustyErrorType> {
+    // chrono panics on bad format strings while formatting, so they are checked up front
+    let items: Vec<Item> = StrftimeItems::new(fmt).collect();
+    if items.iter().any(|i| matches!(i, Item::Error)) {
+        eval_error!(format!("{}() invalid format string '{}'", func, fmt))
+    }
+    Ok(items)
+}
+
+
+pub fn _format(_ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // format(timestamp, fmt, zone?) with strftime style fmt, e.g. '%Y-%m-%d %H:%M:%S'
+    func_nargs_ge!(args, 2);
+    func_nargs_le!(args, 3);
+    let ts: f64 = arg("format", args, 0)?;
+    let fmt: String = arg("format", args, 1)?;
+    let zone = _zone("format", args, 2)?;
+    let items = _items("format", &fmt)?;
+
+    let secs = ts.floor();
+    let nanos = ((ts - secs) * 1e9) as u32;
+    let utc = match DateTime::<Utc>::from_timestamp(secs as i64, nanos) {
+        Some(t) if ts.is_finite() => t,
+        _ => eval_error!(format!("format() timestamp {} out of range", ts))
+    };
+    let text = match zone {
+        Zone::Utc => utc.format_with_items(items.into_iter()).to_string(),
+        Zone::Local => utc.with_timezone(&Local).format_with_items(items.into_iter()).to_string(),
+    };
+    Ok(Block::Object(Token::Text(text)))
+}
+
+pub fn _parse(_ns: &mut NameSpace, args: &Vec<Block>) -> Result<Block, KrustyErrorType> {
+    // parse(text, fmt, zone?) returns a unix timestamp, the zone is ignored if fmt contains an offset (%z)
+    func_nargs_ge!(args, 2);
+    func_nargs_le!(args, 3);
+    let text: String = arg("parse", args, 0)?;
+    let fmt: String = arg("parse", args, 1)?;
+    let zone = _zone("parse", args, 2)?;
+    _items("parse", &fmt)?;
+
+    if let Ok(t) = DateTime::parse_from_str(&text, &fmt) {
+        return Ok(Block::Object(Token::Number(t.timestamp_micros() as f64 / 1e6)));
+    }
+    let naive = match NaiveDateTime::parse_from_str(&text, &fmt) {
+        Ok(t) => t,
+        Err(e) => match NaiveDate::parse_from_str(&text, &fmt) {
+            // date only formats start at midnight
+            Ok(d) => d.and_hms_opt(0, 0, 0).ok_or("invalid date")?,
+            Err(_) => eval_error!(format!("parse() cannot parse '{}' with format '{}': {}", text, fmt, e))
+        }
+    };
+    let micros = match zone {
+        Zone::Utc => Utc.from_utc_datetime(&naive).timestamp_micros(),
+        Zone::Local => match Local.from_local_datetime(&naive).single() {
+            Some(t) => t.timestamp_micros(),
+            None => eval_error!(format!("parse() '{}' is ambiguous or does not exist in local time", text))
+        },
+    };
+    Ok(Block::Object(Token::Number(micros as f64 / 1e6)))
+}
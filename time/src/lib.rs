@@ -0,0 +1,27 @@
+#[macro_use] extern crate krusty_core;
+use krusty_core::lib::{moddef, helper};
+
+pub mod clock;
+pub mod datetime;
+
+plugin_metadata!("time");
+
+#[no_mangle]
+pub fn load_all(m_vars: &mut moddef::ModuleVars) {
+    helper::load_typed(m_vars, "now", clock::_now);
+    helper::load_typed(m_vars, "monotonic", clock::_monotonic);
+    helper::load_func(m_vars, "sleep", clock::_sleep);
+    helper::load_typed(m_vars, "stopwatch", clock::_stopwatch);
+    // calendar time
+    helper::load_func(m_vars, "format", datetime::_format);
+    helper::load_func(m_vars, "parse", datetime::_parse);
+}
+
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn it_works() {
+        assert_eq!(2 + 2, 4);
+    }
+}